@@ -0,0 +1,55 @@
+//! Using the asynchronous driver from an Embassy-style task.
+//!
+//! Run with `cargo run --example async --features async`.
+//! This example needs Rust 1.85 for `Waker::noop()`.
+//!
+//! In an Embassy application `sensor_task` would be an
+//! `#[embassy_executor::task]` and the bus would be the asynchronous I2C
+//! peripheral of the HAL. Here a mock bus and a minimal executor are used
+//! so that the example runs on any host.
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+use embedded_hal_async::i2c::I2c;
+use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+use xca9548a::asynch::Xca9548a;
+use xca9548a::{Error, SlaveAddr};
+
+const SENSOR_ADDRESS: u8 = 0x48;
+const TEMPERATURE_REGISTER: u8 = 0x00;
+
+async fn sensor_task<I2C, E>(switch: &mut Xca9548a<I2C>) -> Result<[u8; 2], Error<E>>
+where
+    I2C: I2c<Error = E>,
+    E: embedded_hal_async::i2c::Error,
+{
+    // Sensor connected to channel 3
+    switch.select_channels(0b0000_1000).await?;
+    let mut temperature = [0; 2];
+    switch
+        .write_read(SENSOR_ADDRESS, &[TEMPERATURE_REGISTER], &mut temperature)
+        .await?;
+    Ok(temperature)
+}
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut context = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+fn main() {
+    let expectations = [
+        Transaction::write(0x70, vec![0b0000_1000]),
+        Transaction::write_read(SENSOR_ADDRESS, vec![TEMPERATURE_REGISTER], vec![0x19, 0x80]),
+    ];
+    let mut switch = Xca9548a::new(Mock::new(&expectations), SlaveAddr::default());
+    let temperature = block_on(sensor_task(&mut switch)).unwrap();
+    println!("Temperature register: {:?}", temperature);
+    switch.destroy().done();
+}
@@ -0,0 +1,30 @@
+//! Formatting driver types with `defmt`.
+//!
+//! Run with `cargo build --example defmt --features defmt`.
+//! A real application gets its global logger from a crate such as
+//! `defmt-rtt`. A no-op logger and timestamp are defined here so that the
+//! example links on any target.
+
+extern crate defmt;
+extern crate xca9548a;
+
+use xca9548a::{Error, SlaveAddr};
+
+#[defmt::global_logger]
+struct Logger;
+
+unsafe impl defmt::Logger for Logger {
+    fn acquire() {}
+    unsafe fn flush() {}
+    unsafe fn release() {}
+    unsafe fn write(_bytes: &[u8]) {}
+}
+
+defmt::timestamp!("{=u32}", 0);
+
+fn main() {
+    let address = SlaveAddr::Alternative(false, false, true);
+    let error: Error<u8> = Error::CouldNotAcquireDevice;
+    defmt::info!("I2C switch at {}", address);
+    defmt::error!("switch error: {}", error);
+}
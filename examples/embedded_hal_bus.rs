@@ -0,0 +1,88 @@
+//! Sharing the upstream bus and the switch channels with `embedded-hal-bus`.
+//!
+//! Run with `cargo run --example embedded_hal_bus --features eh1`.
+//!
+//! This is the `embedded-hal` 1.0 counterpart of the `shared_bus` example.
+//! The upstream bus is shared with a `RefCellDevice`: one goes to the switch
+//! and another one to a sensor connected directly to the upstream bus.
+//! Below the switch, a slave I2C device is itself shared between two
+//! sensors connected to the same channel with `AtomicDevice`.
+//! A mock bus is used so that the example runs on any host.
+
+use core::cell::RefCell;
+use embedded_hal_1::i2c::I2c;
+use embedded_hal_bus::i2c::{AtomicDevice, RefCellDevice};
+use embedded_hal_bus::util::AtomicCell;
+use embedded_hal_mock::eh1::i2c::{Mock, Transaction};
+use xca9548a::{SlaveAddr, Xca9548a};
+
+const SWITCH_ADDRESS: u8 = 0x70;
+const UPSTREAM_SENSOR_ADDRESS: u8 = 0x48;
+const SENSOR_A_ADDRESS: u8 = 0x49;
+const SENSOR_B_ADDRESS: u8 = 0x4A;
+const TEMPERATURE_REGISTER: u8 = 0x00;
+
+/// Minimal sensor driver taking ownership of an I2C bus.
+struct Sensor<I2C> {
+    i2c: I2C,
+    address: u8,
+}
+
+impl<I2C: I2c> Sensor<I2C> {
+    fn read_temperature(&mut self) -> Result<[u8; 2], I2C::Error> {
+        let mut data = [0; 2];
+        self.i2c
+            .write_read(self.address, &[TEMPERATURE_REGISTER], &mut data)?;
+        Ok(data)
+    }
+}
+
+fn main() {
+    let expectations = [
+        Transaction::write_read(
+            UPSTREAM_SENSOR_ADDRESS,
+            vec![TEMPERATURE_REGISTER],
+            vec![0x19, 0x00],
+        ),
+        Transaction::write(SWITCH_ADDRESS, vec![0b0000_0001]),
+        Transaction::write_read(
+            SENSOR_A_ADDRESS,
+            vec![TEMPERATURE_REGISTER],
+            vec![0x1A, 0x00],
+        ),
+        Transaction::write_read(
+            SENSOR_B_ADDRESS,
+            vec![TEMPERATURE_REGISTER],
+            vec![0x1B, 0x00],
+        ),
+    ];
+    let mut bus = Mock::new(&expectations);
+    {
+        // Upstream bus shared between the switch and a sensor
+        let upstream = RefCell::new(bus.clone());
+        let mut upstream_sensor = Sensor {
+            i2c: RefCellDevice::new(&upstream),
+            address: UPSTREAM_SENSOR_ADDRESS,
+        };
+        let switch = Xca9548a::new(RefCellDevice::new(&upstream), SlaveAddr::default());
+        println!(
+            "Upstream: {:?}",
+            upstream_sensor.read_temperature().unwrap()
+        );
+
+        // Channel 0 shared between two sensors
+        let parts = switch.split();
+        let channel0 = AtomicCell::new(parts.i2c0);
+        let mut sensor_a = Sensor {
+            i2c: AtomicDevice::new(&channel0),
+            address: SENSOR_A_ADDRESS,
+        };
+        let mut sensor_b = Sensor {
+            i2c: AtomicDevice::new(&channel0),
+            address: SENSOR_B_ADDRESS,
+        };
+        println!("Channel 0, A: {:?}", sensor_a.read_temperature().unwrap());
+        println!("Channel 0, B: {:?}", sensor_b.read_temperature().unwrap());
+    }
+    bus.done();
+}
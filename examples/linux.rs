@@ -63,4 +63,4 @@ where
     pub fn do_something(&mut self) -> Result<(), Error<E>> {
         self.i2c.write(0x21, &[0x01, 0x02]).map_err(Error::I2C)
     }
-}
\ No newline at end of file
+}
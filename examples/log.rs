@@ -0,0 +1,52 @@
+//! Tracing the channel selections with `log`.
+//!
+//! Run with `cargo run --example log --features log`.
+//! A real application gets its logger from a crate such as `env_logger`.
+//! A minimal logger printing to the standard output is defined here.
+//! A mock bus is used so that the example runs on any host.
+
+use embedded_hal_mock::eh0::i2c::{Mock, Transaction};
+use embedded_hal_mock::eh0::MockError;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::io::ErrorKind;
+use xca9548a::{SlaveAddr, Xca9548a};
+
+const SWITCH_ADDRESS: u8 = 0x70;
+
+struct StdoutLogger;
+
+impl Log for StdoutLogger {
+    fn enabled(&self, _metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        println!("{:<5} {}", record.level(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StdoutLogger = StdoutLogger;
+
+fn main() {
+    log::set_logger(&LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Trace);
+
+    let expectations = [
+        Transaction::write(SWITCH_ADDRESS, vec![0b0000_0001]),
+        Transaction::write(SWITCH_ADDRESS, vec![0b0000_0110]),
+        Transaction::write(SWITCH_ADDRESS, vec![0b0000_1000])
+            .with_error(MockError::Io(ErrorKind::Other)),
+    ];
+    let mut switch = Xca9548a::new(Mock::new(&expectations), SlaveAddr::default());
+
+    // Prints: TRACE device 0x70: channel selection Some(0) -> 0b00000001
+    switch.select_channels(0b0000_0001).unwrap();
+    // Prints: TRACE device 0x70: channel selection Some(1) -> 0b00000110
+    switch.select_channels(0b0000_0110).unwrap();
+    // Prints: WARN  device 0x70: writing channel selection 0b00001000 failed
+    switch.select_channels(0b0000_1000).unwrap_err();
+
+    switch.destroy().done();
+}
@@ -0,0 +1,170 @@
+//! Asynchronous drivers based on the [`embedded-hal-async`] I2C trait.
+//!
+//! [`embedded-hal-async`]: https://github.com/rust-embedded/embedded-hal
+//!
+//! These drivers own the I2C bus directly and take `&mut self`, since
+//! sharing the bus through a `RefCell` across `.await` points is not sound.
+//! For the same reason, they cannot be split into slave I2C devices.
+//!
+//! Each driver implements the `embedded_hal_async::i2c::I2c` trait, so it
+//! can be handed to any asynchronous driver of a device connected to the
+//! currently selected channels. As with the blocking drivers, forwarding a
+//! transfer to the address of the device returns `Error::AddressConflict`
+//! unless `Config::allow_own_address` is set.
+
+use embedded_hal_async::i2c::{self, ErrorType, I2c, Operation, SevenBitAddress};
+
+use crate::{check_channels, Channels, Config, Error, SlaveAddr, DEVICE_BASE_ADDRESS};
+
+macro_rules! impl_async_device {
+    ( $name:ident, $doc:expr, $mask:expr ) => {
+        #[doc = $doc]
+        #[derive(Debug)]
+        pub struct $name<I2C> {
+            i2c: I2C,
+            address: u8,
+            config: Config,
+        }
+
+        impl<I2C> $name<I2C> {
+            /// Create new instance of the device
+            pub fn new(i2c: I2C, address: SlaveAddr) -> Self {
+                Self::new_with_config(i2c, address, Config::default())
+            }
+
+            /// Create new instance of the device with the given configuration
+            ///
+            /// Only `Config::allow_own_address` applies to the asynchronous
+            /// drivers.
+            pub fn new_with_config(i2c: I2C, address: SlaveAddr, config: Config) -> Self {
+                $name {
+                    i2c,
+                    address: address.addr(DEVICE_BASE_ADDRESS),
+                    config,
+                }
+            }
+
+            /// Get the resolved 7-bit I²C address of the device.
+            ///
+            /// This does not communicate with the device.
+            pub fn address(&self) -> u8 {
+                self.address
+            }
+
+            /// Destroy driver instance, return I²C bus instance.
+            pub fn destroy(self) -> I2C {
+                self.i2c
+            }
+
+            /// Check that a slave address does not conflict with the device address.
+            fn check_address<E>(&self, address: SevenBitAddress) -> Result<(), Error<E>> {
+                if address == self.address && !self.config.allow_own_address {
+                    Err(Error::AddressConflict)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+
+        impl<I2C, E> $name<I2C>
+        where
+            I2C: I2c<SevenBitAddress, Error = E>,
+        {
+            /// Select which channels are enabled.
+            ///
+            /// Each bit corresponds to a channel.
+            /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
+            /// A `0` disables the channel and a `1` enables it.
+            /// Several channels can be enabled at the same time.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub async fn select_channels(
+                &mut self,
+                channels: impl Into<Channels>,
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $mask)?;
+                self.i2c
+                    .write(self.address, &[channels])
+                    .await
+                    .map_err(Error::I2C)
+            }
+
+            /// Get status of channels.
+            ///
+            /// Each bit corresponds to a channel.
+            /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
+            /// A `0` means the channel is disabled and a `1` that the channel is enabled.
+            pub async fn get_channel_status(&mut self) -> Result<u8, Error<E>> {
+                let mut data = [0];
+                self.i2c
+                    .read(self.address, &mut data)
+                    .await
+                    .map_err(Error::I2C)?;
+                Ok(data[0] & $mask)
+            }
+        }
+
+        impl<I2C, E> ErrorType for $name<I2C>
+        where
+            I2C: ErrorType<Error = E>,
+            E: i2c::Error,
+        {
+            type Error = Error<E>;
+        }
+
+        impl<I2C, E> I2c<SevenBitAddress> for $name<I2C>
+        where
+            I2C: I2c<SevenBitAddress, Error = E>,
+            E: i2c::Error,
+        {
+            async fn read(
+                &mut self,
+                address: SevenBitAddress,
+                buffer: &mut [u8],
+            ) -> Result<(), Self::Error> {
+                self.check_address(address)?;
+                self.i2c.read(address, buffer).await.map_err(Error::I2C)
+            }
+
+            async fn write(
+                &mut self,
+                address: SevenBitAddress,
+                bytes: &[u8],
+            ) -> Result<(), Self::Error> {
+                self.check_address(address)?;
+                self.i2c.write(address, bytes).await.map_err(Error::I2C)
+            }
+
+            async fn write_read(
+                &mut self,
+                address: SevenBitAddress,
+                bytes: &[u8],
+                buffer: &mut [u8],
+            ) -> Result<(), Self::Error> {
+                self.check_address(address)?;
+                self.i2c
+                    .write_read(address, bytes, buffer)
+                    .await
+                    .map_err(Error::I2C)
+            }
+
+            async fn transaction(
+                &mut self,
+                address: SevenBitAddress,
+                operations: &mut [Operation<'_>],
+            ) -> Result<(), Self::Error> {
+                self.check_address(address)?;
+                self.i2c
+                    .transaction(address, operations)
+                    .await
+                    .map_err(Error::I2C)
+            }
+        }
+    };
+}
+
+impl_async_device!(Xca9548a, "Asynchronous device driver for T/PCA9548A", 0xff);
+impl_async_device!(Xca9543a, "Asynchronous device driver for T/PCA9543A", 0x03);
+impl_async_device!(Xca9545a, "Asynchronous device driver for T/PCA9545A", 0x0f);
+impl_async_device!(Xca9546a, "Asynchronous device driver for T/PCA9546A", 0x0f);
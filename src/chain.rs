@@ -0,0 +1,114 @@
+use super::{Channel, Error, Xca9548a};
+use alloc::vec::Vec;
+use hal::blocking::i2c;
+
+/// Number of channels of each device in the chain
+const CHANNEL_COUNT: usize = 8;
+
+/// Chain of T/PCA9548A devices sharing the same upstream bus
+///
+/// The channels of all devices are numbered as logical buses: logical bus
+/// `n` is channel `n % 8` of the device at index `n / 8`.
+/// Before a channel is selected, all channels of the other devices are
+/// disabled, so only one logical bus is connected at a time.
+///
+/// This requires the `alloc` feature.
+pub struct MuxChain<I2C> {
+    muxes: Vec<Xca9548a<I2C>>,
+}
+
+impl<I2C> MuxChain<I2C> {
+    /// Create new chain from the given devices
+    pub fn new(muxes: Vec<Xca9548a<I2C>>) -> Self {
+        MuxChain { muxes }
+    }
+
+    /// Add a device at the end of the chain.
+    ///
+    /// Its channels are the next 8 logical buses.
+    pub fn push(&mut self, mux: Xca9548a<I2C>) {
+        self.muxes.push(mux);
+    }
+
+    /// Get the number of logical buses in the chain.
+    pub fn bus_count(&self) -> usize {
+        self.muxes.len() * CHANNEL_COUNT
+    }
+
+    /// Get the device index and the channel of a logical bus, if it exists.
+    pub fn route(&self, bus: usize) -> Option<(usize, Channel)> {
+        if bus < self.bus_count() {
+            let channel = Channel::from_index((bus % CHANNEL_COUNT) as u8)?;
+            Some((bus / CHANNEL_COUNT, channel))
+        } else {
+            None
+        }
+    }
+
+    /// Destroy the chain, return the devices.
+    pub fn destroy(self) -> Vec<Xca9548a<I2C>> {
+        self.muxes
+    }
+}
+
+impl<I2C, E> MuxChain<I2C>
+where
+    I2C: i2c::Write<Error = E>,
+{
+    /// Connect a logical bus, disabling all other logical buses.
+    ///
+    /// Selecting a logical bus that does not exist returns
+    /// `Error::InvalidChannelMask`.
+    pub fn select_bus(&mut self, bus: usize) -> Result<&mut Xca9548a<I2C>, Error<E>> {
+        let (index, channel) = self.route(bus).ok_or(Error::InvalidChannelMask)?;
+        for (i, mux) in self.muxes.iter_mut().enumerate() {
+            if i != index {
+                mux.select_channels(0)?;
+            }
+        }
+        let mux = &mut self.muxes[index];
+        mux.select_channel(channel)?;
+        Ok(mux)
+    }
+
+    /// Connect a logical bus and write to a slave connected to it.
+    ///
+    /// Selecting a logical bus that does not exist returns
+    /// `Error::InvalidChannelMask`.
+    pub fn write(&mut self, bus: usize, address: u8, bytes: &[u8]) -> Result<(), Error<E>> {
+        i2c::Write::write(self.select_bus(bus)?, address, bytes)
+    }
+}
+
+impl<I2C, E> MuxChain<I2C>
+where
+    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+{
+    /// Connect a logical bus and read from a slave connected to it.
+    ///
+    /// Selecting a logical bus that does not exist returns
+    /// `Error::InvalidChannelMask`.
+    pub fn read(&mut self, bus: usize, address: u8, buffer: &mut [u8]) -> Result<(), Error<E>> {
+        i2c::Read::read(self.select_bus(bus)?, address, buffer)
+    }
+}
+
+impl<I2C, E> MuxChain<I2C>
+where
+    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+{
+    /// Connect a logical bus and write to and then read from a slave
+    /// connected to it.
+    ///
+    /// Selecting a logical bus that does not exist returns
+    /// `Error::InvalidChannelMask`.
+    pub fn write_read(
+        &mut self,
+        bus: usize,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+    ) -> Result<(), Error<E>> {
+        i2c::WriteRead::write_read(self.select_bus(bus)?, address, bytes, buffer)
+    }
+}
@@ -0,0 +1,361 @@
+use core::convert::TryFrom;
+use core::{fmt, ops, str};
+
+/// I2C switch channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Channel {
+    /// Channel 0 (SD0/SC0 pins)
+    Ch0 = 0,
+    /// Channel 1 (SD1/SC1 pins)
+    Ch1 = 1,
+    /// Channel 2 (SD2/SC2 pins)
+    Ch2 = 2,
+    /// Channel 3 (SD3/SC3 pins)
+    Ch3 = 3,
+    /// Channel 4 (SD4/SC4 pins)
+    Ch4 = 4,
+    /// Channel 5 (SD5/SC5 pins)
+    Ch5 = 5,
+    /// Channel 6 (SD6/SC6 pins)
+    Ch6 = 6,
+    /// Channel 7 (SD7/SC7 pins)
+    Ch7 = 7,
+}
+
+impl Channel {
+    /// Control register bit mask selecting only this channel
+    pub(crate) const fn mask(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// Iterate over the channels enabled in a channel mask, in ascending order.
+    ///
+    /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on. This is
+    /// useful together with `get_channel_status()`.
+    pub fn iter_from_mask(mask: u8) -> impl Iterator<Item = Channel> {
+        (0..8)
+            .filter(move |index| mask & (1 << index) != 0)
+            .filter_map(Channel::from_index)
+    }
+
+    /// Channel with the given number, if any
+    pub(crate) fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(Channel::Ch0),
+            1 => Some(Channel::Ch1),
+            2 => Some(Channel::Ch2),
+            3 => Some(Channel::Ch3),
+            4 => Some(Channel::Ch4),
+            5 => Some(Channel::Ch5),
+            6 => Some(Channel::Ch6),
+            7 => Some(Channel::Ch7),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Channel {
+    /// Print the channel as `ch0` to `ch7`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ch{}", *self as u8)
+    }
+}
+
+impl str::FromStr for Channel {
+    type Err = ();
+
+    /// Parse a channel from `0` to `7` or `ch0` to `ch7`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let index = s.strip_prefix("ch").unwrap_or(s);
+        match index.as_bytes() {
+            [digit @ b'0'..=b'7'] => Channel::from_index(digit - b'0').ok_or(()),
+            _ => Err(()),
+        }
+    }
+}
+
+impl TryFrom<u8> for Channel {
+    type Error = ();
+
+    /// Convert a channel number from `0` to `7`
+    fn try_from(index: u8) -> Result<Self, Self::Error> {
+        Channel::from_index(index).ok_or(())
+    }
+}
+
+impl From<Channel> for u8 {
+    /// Get the channel number
+    fn from(channel: Channel) -> Self {
+        channel as u8
+    }
+}
+
+/// Build the channel mask enabling the given channels.
+///
+/// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on. Channels
+/// given several times are enabled once. This does not depend on any
+/// device, so it can be used in constants and tested in isolation.
+pub const fn mask_from_channels(channels: &[Channel]) -> u8 {
+    let mut mask = 0;
+    let mut index = 0;
+    while index < channels.len() {
+        mask |= channels[index].mask();
+        index += 1;
+    }
+    mask
+}
+
+/// Iterate over the channels enabled in a channel mask, in ascending order.
+///
+/// This is the inverse of [`mask_from_channels()`].
+/// Same as [`Channel::iter_from_mask()`].
+pub fn channels_from_mask(mask: u8) -> impl Iterator<Item = Channel> {
+    Channel::iter_from_mask(mask)
+}
+
+/// Set of I2C switch channels
+///
+/// Channel sets can be combined with the `|`, `&` and `!` operators and
+/// converted from and to the raw `u8` channel mask, where bit 0 corresponds
+/// to channel 0, bit 1 to channel 1 and so on.
+/// All methods taking a channel mask accept `Channels`, a single `Channel`
+/// and a raw `u8` mask. A `Channel` gives the mask of that channel alone,
+/// so `select_channels(Channel::Ch3)` enables only channel 3.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Channels(u8);
+
+impl Channels {
+    /// No channel
+    pub const NONE: Channels = Channels(0);
+    /// Channel 0 (SD0/SC0 pins)
+    pub const CH0: Channels = Channels(1 << 0);
+    /// Channel 1 (SD1/SC1 pins)
+    pub const CH1: Channels = Channels(1 << 1);
+    /// Channel 2 (SD2/SC2 pins)
+    pub const CH2: Channels = Channels(1 << 2);
+    /// Channel 3 (SD3/SC3 pins)
+    pub const CH3: Channels = Channels(1 << 3);
+    /// Channel 4 (SD4/SC4 pins)
+    pub const CH4: Channels = Channels(1 << 4);
+    /// Channel 5 (SD5/SC5 pins)
+    pub const CH5: Channels = Channels(1 << 5);
+    /// Channel 6 (SD6/SC6 pins)
+    pub const CH6: Channels = Channels(1 << 6);
+    /// Channel 7 (SD7/SC7 pins)
+    pub const CH7: Channels = Channels(1 << 7);
+    /// All channels
+    pub const ALL: Channels = Channels(0xff);
+
+    /// Get the raw channel mask
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Check whether all the given channels are in the set
+    pub fn contains(self, other: Channels) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Iterate over the channels in the set, in ascending order
+    pub fn iter(self) -> impl Iterator<Item = Channel> {
+        Channel::iter_from_mask(self.0)
+    }
+}
+
+impl From<u8> for Channels {
+    fn from(mask: u8) -> Self {
+        Channels(mask)
+    }
+}
+
+impl From<Channels> for u8 {
+    fn from(channels: Channels) -> Self {
+        channels.0
+    }
+}
+
+impl From<Channel> for Channels {
+    fn from(channel: Channel) -> Self {
+        Channels(channel.mask())
+    }
+}
+
+impl ops::BitOr for Channels {
+    type Output = Channels;
+
+    fn bitor(self, other: Channels) -> Channels {
+        Channels(self.0 | other.0)
+    }
+}
+
+impl ops::BitOrAssign for Channels {
+    fn bitor_assign(&mut self, other: Channels) {
+        self.0 |= other.0;
+    }
+}
+
+impl ops::BitAnd for Channels {
+    type Output = Channels;
+
+    fn bitand(self, other: Channels) -> Channels {
+        Channels(self.0 & other.0)
+    }
+}
+
+impl ops::BitAndAssign for Channels {
+    fn bitand_assign(&mut self, other: Channels) {
+        self.0 &= other.0;
+    }
+}
+
+impl ops::Not for Channels {
+    type Output = Channels;
+
+    fn not(self) -> Channels {
+        Channels(!self.0)
+    }
+}
+
+/// Decoded control register of the devices with interrupt inputs
+///
+/// Get it with `get_status()`. The channel and interrupt bits are parsed
+/// according to the layout of the device.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Status {
+    channels: Channels,
+    interrupts: Channels,
+}
+
+impl Status {
+    pub(crate) fn new(channels: u8, interrupts: u8) -> Self {
+        Status {
+            channels: Channels(channels),
+            interrupts: Channels(interrupts),
+        }
+    }
+
+    /// Check whether a channel is enabled
+    pub fn channel_enabled(self, channel: Channel) -> bool {
+        self.channels.contains(channel.into())
+    }
+
+    /// Check whether the interrupt input of a channel is asserted
+    pub fn interrupt_pending(self, channel: Channel) -> bool {
+        self.interrupts.contains(channel.into())
+    }
+
+    /// Get the enabled channels
+    pub fn channels(self) -> Channels {
+        self.channels
+    }
+
+    /// Get the channels with an asserted interrupt input
+    pub fn interrupts(self) -> Channels {
+        self.interrupts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_get_channel_mask() {
+        assert_eq!(0b0000_0001, Channel::Ch0.mask());
+        assert_eq!(0b0000_1000, Channel::Ch3.mask());
+        assert_eq!(0b1000_0000, Channel::Ch7.mask());
+    }
+
+    #[test]
+    fn can_get_channel_from_index() {
+        assert_eq!(Some(Channel::Ch0), Channel::from_index(0));
+        assert_eq!(Some(Channel::Ch7), Channel::from_index(7));
+        assert_eq!(None, Channel::from_index(8));
+    }
+
+    #[test]
+    fn can_convert_channel_from_and_to_number() {
+        for index in 0..8 {
+            let channel = Channel::try_from(index).unwrap();
+            assert_eq!(index, u8::from(channel));
+        }
+        assert_eq!(Ok(Channel::Ch4), Channel::try_from(4));
+    }
+
+    #[test]
+    fn rejects_channel_number_out_of_range() {
+        assert_eq!(Err(()), Channel::try_from(8));
+        assert_eq!(Err(()), Channel::try_from(0xff));
+    }
+
+    #[test]
+    fn can_build_mask_from_channels() {
+        const MASK: u8 = mask_from_channels(&[Channel::Ch0, Channel::Ch5]);
+        assert_eq!(0b0010_0001, MASK);
+        assert_eq!(0, mask_from_channels(&[]));
+        assert_eq!(
+            0b1000_0000,
+            mask_from_channels(&[Channel::Ch7, Channel::Ch7])
+        );
+    }
+
+    #[test]
+    fn can_iterate_channels_from_mask() {
+        let mut channels = Channel::iter_from_mask(0b0010_0101);
+        assert_eq!(Some(Channel::Ch0), channels.next());
+        assert_eq!(Some(Channel::Ch2), channels.next());
+        assert_eq!(Some(Channel::Ch5), channels.next());
+        assert_eq!(None, channels.next());
+        assert_eq!(None, Channel::iter_from_mask(0).next());
+        assert_eq!(8, Channel::iter_from_mask(0xff).count());
+    }
+
+    #[test]
+    fn can_format_channel() {
+        extern crate std;
+        use std::string::ToString;
+        assert_eq!("ch0", Channel::Ch0.to_string());
+        assert_eq!("ch7", Channel::Ch7.to_string());
+        assert_eq!(Ok(Channel::Ch5), Channel::Ch5.to_string().parse());
+    }
+
+    #[test]
+    fn can_parse_channel() {
+        assert_eq!(Ok(Channel::Ch0), "0".parse());
+        assert_eq!(Ok(Channel::Ch7), "7".parse());
+        assert_eq!(Ok(Channel::Ch3), "ch3".parse());
+    }
+
+    #[test]
+    fn rejects_invalid_channel() {
+        for s in ["", "8", "ch8", "ch", "ch-1", "CH0", "00", "ch01", " 1"] {
+            assert_eq!(Err(()), s.parse::<Channel>());
+        }
+    }
+
+    #[test]
+    fn can_combine_channels() {
+        let mut channels = Channels::CH0 | Channels::CH4;
+        assert_eq!(0b0001_0001, channels.bits());
+        assert!(channels.contains(Channels::CH4));
+        assert!(!channels.contains(Channels::CH1));
+        channels &= !Channels::CH0;
+        assert_eq!(Channels::CH4, channels);
+        channels |= Channels::CH7;
+        assert_eq!(0b1001_0000, channels.bits());
+        assert_eq!(Channels::NONE, Channels::CH0 & Channels::CH1);
+        assert_eq!(Channels::ALL, !Channels::NONE);
+    }
+
+    #[test]
+    fn can_convert_channels() {
+        assert_eq!(Channels::CH0 | Channels::CH2, Channels::from(0b0000_0101));
+        assert_eq!(0b1000_0001, u8::from(Channels::CH0 | Channels::CH7));
+        assert_eq!(Channels::CH3, Channels::from(Channel::Ch3));
+        let mut iter = (Channels::CH1 | Channels::CH6).iter();
+        assert_eq!(Some(Channel::Ch1), iter.next());
+        assert_eq!(Some(Channel::Ch6), iter.next());
+        assert_eq!(None, iter.next());
+    }
+}
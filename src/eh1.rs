@@ -0,0 +1,261 @@
+//! Implementation of the [`embedded-hal`] 1.0 I2C trait.
+//!
+//! [`embedded-hal`]: https://github.com/rust-embedded/embedded-hal
+//!
+//! The devices forward all operations to the I2C bus transparently.
+//! The slave I2C devices, borrowed or owned, select their channel before
+//! delegating the operations to the I2C bus.
+//!
+//! The devices also get `select_channels_eh1()` and
+//! `get_channel_status_eh1()` methods, which work on an `embedded-hal` 1.0
+//! bus. Their channel masks work as in [`I2cSwitch`](crate::I2cSwitch).
+
+use embedded_hal_1::i2c::{
+    self, AddressMode, ErrorKind, ErrorType, I2c, Operation, SevenBitAddress,
+};
+
+use crate::ltc4306::{decode_channels, encode_channels, CHANNELS_MASK, CONNECTION};
+use crate::register::{ControlRegister, Mux2, Mux4, Mux8, Switch2, Switch4, Switch8};
+#[cfg(feature = "alloc")]
+use crate::OwnedI2cSlave;
+use crate::{
+    check_channels, Channels, DeviceMutex, DoOnAcquired, Error, I2cSlave, Ltc4306, Pca9540b,
+    Pca9542a, Pca9547, SlaveAddress, Xca9543a, Xca9544a, Xca9545a, Xca9546a, Xca9548a,
+    Xca954xaData,
+};
+
+impl<E: i2c::Error> i2c::Error for Error<E> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Error::I2C(e) => e.kind(),
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+impl<I2C, E> Xca954xaData<I2C>
+where
+    I2C: I2c<SevenBitAddress, Error = E>,
+{
+    fn select_channels_eh1(&mut self, channels: u8) -> Result<(), Error<E>> {
+        self.update_control_register(channels, |i2c, address, bytes| i2c.write(address, bytes))
+    }
+}
+
+macro_rules! impl_eh1 {
+    ( $name:ident ) => {
+        impl<I2C, M, E> ErrorType for $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: ErrorType<Error = E>,
+            E: i2c::Error,
+        {
+            type Error = Error<E>;
+        }
+
+        impl<I2C, M, E, A> I2c<A> for $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: I2c<A, Error = E>,
+            E: i2c::Error,
+            A: AddressMode + SlaveAddress,
+        {
+            fn read(&mut self, address: A, buffer: &mut [u8]) -> Result<(), Self::Error> {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
+                    dev.i2c.read(address, buffer).map_err(Error::I2C)
+                })
+            }
+
+            fn write(&mut self, address: A, bytes: &[u8]) -> Result<(), Self::Error> {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
+                    dev.i2c.write(address, bytes).map_err(Error::I2C)
+                })
+            }
+
+            fn write_read(
+                &mut self,
+                address: A,
+                bytes: &[u8],
+                buffer: &mut [u8],
+            ) -> Result<(), Self::Error> {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
+                    dev.i2c
+                        .write_read(address, bytes, buffer)
+                        .map_err(Error::I2C)
+                })
+            }
+
+            fn transaction(
+                &mut self,
+                address: A,
+                operations: &mut [Operation<'_>],
+            ) -> Result<(), Self::Error> {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
+                    dev.i2c.transaction(address, operations).map_err(Error::I2C)
+                })
+            }
+        }
+    };
+}
+
+macro_rules! impl_eh1_select {
+    ( $name:ident, $reg:ident ) => {
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: I2c<SevenBitAddress, Error = E>,
+        {
+            /// Select which channels are enabled on an `embedded-hal` 1.0 bus.
+            ///
+            /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`. The multiplexers can enable
+            /// at most one channel, so they return it as well for several
+            /// channels.
+            pub fn select_channels_eh1(
+                &mut self,
+                channels: impl Into<Channels>,
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                // Only a single channel survives the multiplexer encoding
+                if $reg::decode($reg::encode(channels)) != channels {
+                    return Err(Error::InvalidChannelMask);
+                }
+                self.do_on_acquired(|dev| dev.select_channels_eh1($reg::encode(channels)))
+            }
+
+            /// Get the mask of the enabled channels on an `embedded-hal` 1.0 bus.
+            ///
+            /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
+            pub fn get_channel_status_eh1(&self) -> Result<u8, Error<E>> {
+                self.do_on_acquired(|dev| {
+                    let mut data = [0];
+                    dev.i2c.read(dev.address, &mut data).map_err(Error::I2C)?;
+                    Ok($reg::decode(data[0]))
+                })
+            }
+        }
+    };
+}
+
+impl_eh1!(Xca9548a);
+impl_eh1!(Xca9543a);
+impl_eh1!(Xca9545a);
+impl_eh1!(Xca9546a);
+impl_eh1!(Xca9544a);
+impl_eh1!(Pca9542a);
+impl_eh1!(Pca9540b);
+impl_eh1!(Pca9547);
+impl_eh1!(Ltc4306);
+
+impl_eh1_select!(Xca9548a, Switch8);
+impl_eh1_select!(Xca9543a, Switch2);
+impl_eh1_select!(Xca9545a, Switch4);
+impl_eh1_select!(Xca9546a, Switch4);
+impl_eh1_select!(Xca9544a, Mux4);
+impl_eh1_select!(Pca9542a, Mux2);
+impl_eh1_select!(Pca9540b, Mux2);
+impl_eh1_select!(Pca9547, Mux8);
+
+impl<I2C, M, E> Ltc4306<I2C, M>
+where
+    M: DeviceMutex<Xca954xaData<I2C>>,
+    I2C: I2c<SevenBitAddress, Error = E>,
+{
+    /// Select which channels are enabled on an `embedded-hal` 1.0 bus.
+    ///
+    /// Same as [`select_channels()`](#method.select_channels).
+    pub fn select_channels_eh1(&mut self, channels: impl Into<Channels>) -> Result<(), Error<E>> {
+        let channels = check_channels(channels.into().bits(), CHANNELS_MASK)?;
+        self.do_on_acquired(|dev| {
+            dev.update_control_register(encode_channels(channels), |i2c, address, value| {
+                i2c.write(address, &[CONNECTION, value[0]])
+            })
+        })
+    }
+
+    /// Get status of channels on an `embedded-hal` 1.0 bus.
+    ///
+    /// Same as [`get_channel_status()`](#method.get_channel_status).
+    pub fn get_channel_status_eh1(&self) -> Result<u8, Error<E>> {
+        self.do_on_acquired(|dev| {
+            let mut data = [0];
+            dev.i2c
+                .write_read(dev.address, &[CONNECTION], &mut data)
+                .map_err(Error::I2C)?;
+            Ok(decode_channels(data[0]))
+        })
+    }
+}
+
+// The slave I2C devices select their channel before each transfer.
+macro_rules! impl_eh1_slave {
+    ( [$($lt:lifetime)?] $slave:ty ) => {
+        impl<$($lt,)? DEV, I2C, E> ErrorType for $slave
+        where
+            I2C: ErrorType<Error = E>,
+            E: i2c::Error,
+        {
+            type Error = Error<E>;
+        }
+
+        impl<$($lt,)? DEV, I2C, E, A> I2c<A> for $slave
+        where
+            DEV: DoOnAcquired<I2C>,
+            I2C: I2c<SevenBitAddress, Error = E> + I2c<A, Error = E>,
+            E: i2c::Error,
+            A: AddressMode + SlaveAddress,
+        {
+            fn read(&mut self, address: A, buffer: &mut [u8]) -> Result<(), Self::Error> {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels_eh1(self.1)?;
+                    dev.i2c.read(address, buffer).map_err(Error::I2C)
+                })
+            }
+
+            fn write(&mut self, address: A, bytes: &[u8]) -> Result<(), Self::Error> {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels_eh1(self.1)?;
+                    dev.i2c.write(address, bytes).map_err(Error::I2C)
+                })
+            }
+
+            fn write_read(
+                &mut self,
+                address: A,
+                bytes: &[u8],
+                buffer: &mut [u8],
+            ) -> Result<(), Self::Error> {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels_eh1(self.1)?;
+                    dev.i2c
+                        .write_read(address, bytes, buffer)
+                        .map_err(Error::I2C)
+                })
+            }
+
+            fn transaction(
+                &mut self,
+                address: A,
+                operations: &mut [Operation<'_>],
+            ) -> Result<(), Self::Error> {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels_eh1(self.1)?;
+                    dev.i2c.transaction(address, operations).map_err(Error::I2C)
+                })
+            }
+        }
+    };
+}
+
+impl_eh1_slave!(['a] I2cSlave<'a, DEV, I2C>);
+#[cfg(feature = "alloc")]
+impl_eh1_slave!([] OwnedI2cSlave<DEV, I2C>);
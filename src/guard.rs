@@ -0,0 +1,143 @@
+use super::{DoOnAcquired, Error};
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use hal::blocking::i2c;
+
+/// Device wrapper disabling all channels when dropped.
+///
+/// Create it with `disable_on_drop()` on the device. The wrapper can be
+/// used like the device itself, and `into_inner()` returns the device
+/// without disabling the channels.
+///
+/// Dropping the wrapper writes `0x00` to the control register in one bus
+/// transaction, even if no channel is enabled. Since `Drop` cannot return
+/// errors, any error is ignored.
+pub struct DisableOnDrop<DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    // Always `Some` until `into_inner()` takes the device.
+    device: Option<DEV>,
+    _i2c: PhantomData<I2C>,
+}
+
+impl<DEV, I2C> DisableOnDrop<DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    pub(crate) fn new(device: DEV) -> Self {
+        DisableOnDrop {
+            device: Some(device),
+            _i2c: PhantomData,
+        }
+    }
+
+    /// Return the device without disabling its channels.
+    pub fn into_inner(mut self) -> DEV {
+        self.device.take().unwrap()
+    }
+}
+
+impl<DEV, I2C> Deref for DisableOnDrop<DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    type Target = DEV;
+
+    fn deref(&self) -> &DEV {
+        self.device.as_ref().unwrap()
+    }
+}
+
+impl<DEV, I2C> DerefMut for DisableOnDrop<DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    fn deref_mut(&mut self) -> &mut DEV {
+        self.device.as_mut().unwrap()
+    }
+}
+
+impl<DEV, I2C> Drop for DisableOnDrop<DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    fn drop(&mut self) {
+        if let Some(device) = &self.device {
+            let _: Result<(), Error<I2C::Error>> =
+                device.do_on_acquired(|dev| dev.force_select_channels(0));
+        }
+    }
+}
+
+/// Device wrapper restoring the previous channel selection when dropped.
+///
+/// Create it with `select_scoped()` on the device. The wrapper can be used
+/// like the device itself while the selected channels are enabled.
+///
+/// Dropping the wrapper selects the channels that were enabled before
+/// `select_scoped()` was called. Since `Drop` cannot return errors, any
+/// error is ignored. Use `with_channels()` if the restore errors matter.
+pub struct ChannelGuard<'a, DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    device: &'a mut DEV,
+    previous: u8,
+    _i2c: PhantomData<I2C>,
+}
+
+impl<'a, DEV, I2C> ChannelGuard<'a, DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    pub(crate) fn new(device: &'a mut DEV, previous: u8) -> Self {
+        ChannelGuard {
+            device,
+            previous,
+            _i2c: PhantomData,
+        }
+    }
+}
+
+impl<'a, DEV, I2C> Deref for ChannelGuard<'a, DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    type Target = DEV;
+
+    fn deref(&self) -> &DEV {
+        self.device
+    }
+}
+
+impl<'a, DEV, I2C> DerefMut for ChannelGuard<'a, DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    fn deref_mut(&mut self) -> &mut DEV {
+        self.device
+    }
+}
+
+impl<'a, DEV, I2C> Drop for ChannelGuard<'a, DEV, I2C>
+where
+    DEV: DoOnAcquired<I2C>,
+    I2C: i2c::Write,
+{
+    fn drop(&mut self) {
+        let previous = self.previous;
+        let _: Result<(), Error<I2C::Error>> = self
+            .device
+            .do_on_acquired(|dev| dev.force_select_channels(previous));
+    }
+}
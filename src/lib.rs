@@ -5,11 +5,24 @@
 //!
 //! This driver allows you to:
 //! - Enable one or multiple I2C channels. See [`select_channels()`].
+//! - Enable a single I2C channel. See [`select_channel()`].
 //! - Communicate with the slaves connected to the enabled channels transparently.
 //! - Split the device into slave (virtual) I2C devices (one per channel). See: [`split()`].
+//! - Share the device across tasks through a mutex. See: [`DeviceMutex`].
+//! - Disable all channels when the device is dropped. See: [`DisableOnDrop`].
+//! - Use the devices and the slave I2C devices through the `embedded-hal` 1.0
+//!   `I2c` trait with the `eh1` feature. Apart from `select_channels_eh1()`
+//!   and `get_channel_status_eh1()`, the driver methods still require the
+//!   `embedded-hal` 0.2 I2C traits.
+//! - Use the device asynchronously with the `async` feature. See: [`asynch`].
+//! - Wait for channel interrupts on the `INT` pin with the `unproven` feature.
 //!
 //! [`select_channels()`]: struct.Xca9548a.html#method.select_channels
+//! [`select_channel()`]: struct.Xca9548a.html#method.select_channel
 //! [`split()`]: struct.Xca9548a.html#method.split
+//! [`DeviceMutex`]: trait.DeviceMutex.html
+//! [`DisableOnDrop`]: struct.DisableOnDrop.html
+//! [`asynch`]: asynch/index.html
 //!
 //! ## The devices
 //!
@@ -22,18 +35,66 @@
 //! For example, if  eight identical digital temperature sensors are needed in the
 //! application, one sensor can be connected at each channel: 0-N.
 //!
-//! The TCA9545/3A and PCA9545/3A devices have an assosciated interrupt pin `INT` for each channel
+//! The PCA9547 is a multiplexer instead. Only one of its eight channels
+//! can be enabled at a time. See [`Pca9547`].
+//! The same applies to the four channels of the TCA9544A and PCA9544A
+//! and the two channels of the PCA9542A and PCA9540B.
+//! See [`Xca9544a`], [`Pca9542a`] and [`Pca9540b`].
+//!
+//! [`Pca9547`]: struct.Pca9547.html
+//! [`Xca9544a`]: struct.Xca9544a.html
+//! [`Pca9542a`]: struct.Pca9542a.html
+//! [`Pca9540b`]: struct.Pca9540b.html
+//!
+//! The LTC4306 is a four-channel switch with a register map instead of a
+//! single control register. It also reports stuck buses. See [`Ltc4306`].
+//!
+//! [`Ltc4306`]: struct.Ltc4306.html
+//!
+//! The TCA9545/4/3A and PCA9545/4/3/2A devices have an assosciated interrupt pin `INT` for each channel
 //! which can be polled to check which channels have pending interrupts.
 //! (Tip: Can also be used as general inputs)
 //!
+//! The TCA and PCA versions of each device share the same register model, so
+//! each driver handles both. To record which one is fitted, for example for
+//! logging, create the device with `new_tca()` or `new_pca()` and read it
+//! back with `variant()`. See [`Variant`]. Vendor-specific settling times
+//! can be applied per channel with a `ChannelConfig` pre-transfer delay.
+//!
 //! ### Datasheets
 //! - [TCA9548A](http://www.ti.com/lit/ds/symlink/tca9548a.pdf)
 //! - [PCA9548A](http://www.ti.com/lit/ds/symlink/pca9548a.pdf)
 //! - [TCA9545A](http://www.ti.com/lit/ds/symlink/tca9545a.pdf)
 //! - [PCA9545A](http://www.ti.com/lit/ds/symlink/pca9545a.pdf)
+//! - [TCA9546A](http://www.ti.com/lit/ds/symlink/tca9546a.pdf)
+//! - [PCA9546A](http://www.ti.com/lit/ds/symlink/pca9546a.pdf)
+//! - [PCA9547](https://www.nxp.com/docs/en/data-sheet/PCA9547.pdf)
+//! - [TCA9544A](http://www.ti.com/lit/ds/symlink/tca9544a.pdf)
+//! - [PCA9544A](http://www.ti.com/lit/ds/symlink/pca9544a.pdf)
+//! - [PCA9542A](https://www.nxp.com/docs/en/data-sheet/PCA9542A.pdf)
+//! - [PCA9540B](https://www.nxp.com/docs/en/data-sheet/PCA9540B.pdf)
+//! - [LTC4306](https://www.analog.com/en/products/ltc4306.html)
 //! - [TCA9543A](http://www.ti.com/lit/ds/symlink/tca9543a.pdf)
 //! - [PCA9543A](http://www.ti.com/lit/ds/symlink/pca9543a.pdf)
 //!
+//! ## Features
+//!
+//! All features are optional and the default build is blocking and `no_std`.
+//! The blocking and asynchronous drivers are separate, since they are built
+//! on different `embedded-hal` traits.
+//!
+//! | Feature    | Enables                                                        |
+//! |------------|----------------------------------------------------------------|
+//! | (none)     | Blocking `no_std` drivers on the `embedded-hal` 0.2 I2C traits |
+//! | `std`      | `std::error::Error` for `Error`, `DeviceMutex` for `Mutex`     |
+//! | `alloc`    | `MuxChain` and owned slave devices from `into_channels()`      |
+//! | `metrics`  | `stats()` counting channel switches and transfers              |
+//! | `defmt`    | `defmt::Format` implementations for `Error` and `SlaveAddr`    |
+//! | `log`      | `log` trace of channel selections and warnings on write errors |
+//! | `eh1`      | `embedded-hal` 1.0 `I2c` trait on the devices and slave buses  |
+//! | `async`    | Asynchronous drivers in the `asynch` module (implies `eh1`)    |
+//! | `unproven` | `wait_for_interrupt()`, using the `embedded-hal` `InputPin`    |
+//!
 //! ## Usage examples (see also examples folder)
 //!
 //! To use this driver, import this crate and an `embedded_hal` implementation,
@@ -125,6 +186,30 @@
 //! # }
 //! ```
 //!
+//! ### Reading a register from a slave connected to channel 1
+//!
+//! The channel selection and the repeated-start write-read transaction
+//! are done while acquiring the device only once.
+//!
+//! ```no_run
+//! extern crate linux_embedded_hal as hal;
+//! extern crate xca9548a;
+//!
+//! use xca9548a::{Xca9548a, SlaveAddr};
+//!
+//! # fn main() {
+//! let dev = hal::I2cdev::new("/dev/i2c-1").unwrap();
+//! let mut i2c_switch = Xca9548a::new(dev, SlaveAddr::default());
+//!
+//! let slave_address = 0b010_0000; // example slave address
+//! let register = 0x0A; // example register address
+//! let mut value = [0];
+//! i2c_switch
+//!     .write_read_on_channel(0b0000_0010, slave_address, &[register], &mut value)
+//!     .unwrap();
+//! # }
+//! ```
+//!
 //! ### Splitting into individual I2C devices and passing them into drivers
 //!
 //! Drivers usually take ownership of the I2C device.
@@ -193,6 +278,77 @@
 //! parts.i2c1.read(slave_address, &mut read_data).unwrap();
 //! # }
 //! ```
+//!
+//! ### Destroying the device
+//!
+//! The slave I2C devices borrow the device, so it cannot be destroyed
+//! while they are in use. This is checked at compile time, which is why
+//! `destroy()` cannot fail:
+//!
+//! ```compile_fail
+//! extern crate linux_embedded_hal as hal;
+//! extern crate xca9548a;
+//!
+//! use xca9548a::{Xca9548a, SlaveAddr};
+//!
+//! # fn main() {
+//! let dev = hal::I2cdev::new("/dev/i2c-1").unwrap();
+//! let i2c_switch = Xca9548a::new(dev, SlaveAddr::default());
+//! let parts = i2c_switch.split();
+//! let dev = i2c_switch.destroy();
+//! drop(parts);
+//! # }
+//! ```
+//!
+//! ### Cascading devices
+//!
+//! A slave I2C device can be the bus of another device. Every operation on
+//! the downstream device then selects the upstream channel first, so
+//! slaves behind two levels of devices can be used directly.
+//! The downstream device must have a different address than the upstream
+//! device, since both are reachable when the upstream channel is enabled.
+//!
+//! ```no_run
+//! extern crate embedded_hal;
+//! extern crate linux_embedded_hal as hal;
+//! extern crate xca9548a;
+//!
+//! use embedded_hal::blocking::i2c::Write;
+//! use xca9548a::{Xca9548a, SlaveAddr};
+//!
+//! # fn main() {
+//! let dev = hal::I2cdev::new("/dev/i2c-1").unwrap();
+//! let upstream = Xca9548a::new(dev, SlaveAddr::default());
+//! let upstream_parts = upstream.split();
+//!
+//! let address = SlaveAddr::Alternative(false, false, true);
+//! let downstream = Xca9548a::new(upstream_parts.i2c0, address);
+//! let mut downstream_parts = downstream.split();
+//!
+//! // Selects channel 0 upstream and channel 3 downstream, then writes
+//! downstream_parts.i2c3.write(0x20, &[0xAB, 0xCD]).unwrap();
+//! # }
+//! ```
+//!
+//! ### Sharing the buses with `shared-bus`
+//!
+//! The devices and the slave I2C devices work with the [`shared-bus`] crate
+//! without any adapter. Share the upstream bus at the top and pass one of
+//! its proxies to the device. Below the device, each slave I2C device can be
+//! used as the bus of another bus manager to share a channel between several
+//! drivers. See the `shared_bus` example.
+//!
+//! [`shared-bus`]: https://crates.io/crates/shared-bus
+//!
+//! ### Sharing the buses with `embedded-hal-bus`
+//!
+//! With the `eh1` feature, the devices and the slave I2C devices work in the
+//! same way with [`embedded-hal-bus`], the `embedded-hal` 1.0 replacement of
+//! `shared-bus`. Any of its I2C wrappers can be used, above as well as below
+//! the device: `RefCellDevice`, `CriticalSectionDevice`, `AtomicDevice` and,
+//! with `std`, `MutexDevice`. See the `embedded_hal_bus` example.
+//!
+//! [`embedded-hal-bus`]: https://crates.io/crates/embedded-hal-bus
 
 //!
 
@@ -200,27 +356,120 @@
 #![deny(missing_docs)]
 #![no_std]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+#[cfg(feature = "defmt")]
+extern crate defmt;
 extern crate embedded_hal as hal;
-use core::cell;
+#[cfg(feature = "log")]
+extern crate log;
+#[cfg(feature = "std")]
+extern crate std;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use core::{cell, fmt};
+use hal::blocking::delay::DelayUs;
 use hal::blocking::i2c;
+#[cfg(feature = "unproven")]
+use hal::digital::v2::InputPin;
+use hal::digital::v2::OutputPin;
 
 /// All possible errors in this crate
 #[derive(Debug)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum Error<E> {
     /// I²C bus error
     I2C(E),
     /// Could not acquire device. Maybe it is already acquired.
     CouldNotAcquireDevice,
+    /// The channel mask contains channels that do not exist for the device.
+    InvalidChannelMask,
+    /// The slave address is the same as the address of the device, or it is
+    /// not allowed on the selected channels.
+    ///
+    /// See [`Config::allow_own_address`] and
+    /// [`ChannelConfig::allowed_addresses`].
+    AddressConflict,
+    /// The channel selection read back does not match the one written.
+    VerificationFailed {
+        /// Channel selection written
+        expected: u8,
+        /// Channel selection read back
+        actual: u8,
+    },
+    /// Reading the interrupt pin failed.
+    InterruptPin,
+    /// The data is longer than the driver can handle.
+    ///
+    /// See `MAX_READBACK_LEN` of the switches.
+    BufferTooLong,
+}
+
+impl<E> Error<E> {
+    /// Get the I²C bus error, if this is one.
+    pub fn as_i2c_error(&self) -> Option<&E> {
+        match self {
+            Error::I2C(e) => Some(e),
+            _ => None,
+        }
+    }
+
+    /// Check whether the device could not be acquired.
+    pub fn is_acquire_error(&self) -> bool {
+        matches!(self, Error::CouldNotAcquireDevice)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::I2C(e) => write!(f, "I2C bus error: {}", e),
+            Error::CouldNotAcquireDevice => write!(f, "could not acquire device"),
+            Error::InvalidChannelMask => write!(f, "invalid channel mask"),
+            Error::AddressConflict => {
+                write!(f, "slave address conflicts with device address or channel")
+            }
+            Error::VerificationFailed { expected, actual } => write!(
+                f,
+                "channel selection verification failed: wrote {:#010b}, read {:#010b}",
+                expected, actual
+            ),
+            Error::InterruptPin => write!(f, "could not read interrupt pin"),
+            Error::BufferTooLong => write!(f, "buffer too long"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E: std::error::Error + 'static> std::error::Error for Error<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::I2C(e) => Some(e),
+            Error::CouldNotAcquireDevice
+            | Error::InvalidChannelMask
+            | Error::AddressConflict
+            | Error::VerificationFailed { .. }
+            | Error::InterruptPin
+            | Error::BufferTooLong => None,
+        }
+    }
 }
 
 /// Possible slave addresses
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub enum SlaveAddr {
     /// Default slave address
     Default,
     /// Alternative slave address providing bit values for A2, A1 and A0
     /// Note: Some devices does not have all Ax pins, these should be set to false.
     Alternative(bool, bool, bool),
+    /// Raw 7-bit slave address, used verbatim.
+    ///
+    /// Useful when the device is behind an address translator, so that its
+    /// address is not in the `0b111_0xxx` range. The address is masked to
+    /// 7 bits.
+    Raw(u8),
 }
 
 impl Default for SlaveAddr {
@@ -231,17 +480,179 @@ impl Default for SlaveAddr {
 }
 
 impl SlaveAddr {
-    fn addr(self, default: u8) -> u8 {
+    /// Create a slave address from a 7-bit I²C address.
+    ///
+    /// Returns `None` if the address is not in the `0b111_0xxx` range of the devices.
+    pub fn from_address(address: u8) -> Option<Self> {
+        if address & !0b111 != DEVICE_BASE_ADDRESS {
+            return None;
+        }
+        Some(SlaveAddr::Alternative(
+            address & 0b100 != 0,
+            address & 0b010 != 0,
+            address & 0b001 != 0,
+        ))
+    }
+
+    /// Get the 7-bit I²C address of the TCA954xA and PCA954xA devices.
+    ///
+    /// This is a `const fn`, so it can be used to define addresses in
+    /// `const` items.
+    pub const fn address(self) -> u8 {
+        self.addr(DEVICE_BASE_ADDRESS)
+    }
+
+    const fn addr(self, default: u8) -> u8 {
         match self {
             SlaveAddr::Default => default,
             SlaveAddr::Alternative(a2, a1, a0) => {
                 default | ((a2 as u8) << 2) | ((a1 as u8) << 1) | a0 as u8
             }
+            SlaveAddr::Raw(address) => address & 0x7f,
         }
     }
 }
+impl TryFrom<u8> for SlaveAddr {
+    type Error = ();
+
+    /// Same as [`SlaveAddr::from_address()`].
+    fn try_from(address: u8) -> Result<Self, Self::Error> {
+        SlaveAddr::from_address(address).ok_or(())
+    }
+}
+
 const DEVICE_BASE_ADDRESS: u8 = 0b111_0000;
 
+/// Probe the TCA954xA and PCA954xA device addresses on the bus.
+///
+/// A one-byte control register read is attempted at each address from
+/// `0b111_0000` to `0b111_0111`. The returned array is indexed by the
+/// `A2`–`A0` address bits and contains `true` for each address that
+/// acknowledged. Any error is considered a missing device.
+///
+/// This does not need a device instance, so it can be used to discover
+/// the devices on the bus.
+pub fn scan_for_muxes<I2C: i2c::Read>(i2c: &mut I2C) -> [bool; 8] {
+    let mut found = [false; 8];
+    for (address, found) in (DEVICE_BASE_ADDRESS..).zip(found.iter_mut()) {
+        *found = i2c.read(address, &mut [0]).is_ok();
+    }
+    found
+}
+
+/// Silicon vendor variant of a device
+///
+/// The TCA and PCA versions of a device share the same register model and
+/// the driver handles them alike. The variant is only recorded for higher
+/// layers, for example for logging or vendor-specific settling delays.
+/// See `new_tca()`, `new_pca()` and `variant()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Variant {
+    /// Texas Instruments TCA version
+    Tca,
+    /// NXP PCA version
+    Pca,
+}
+
+/// Device configuration
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Allow talking to slaves at the address of the device itself.
+    ///
+    /// By default, forwarding a transaction to the address of the device
+    /// returns `Error::AddressConflict`, since it would change the channel
+    /// selection. Set this to `true` for raw access to the control register.
+    /// The cached channel selection is then discarded on every such access.
+    pub allow_own_address: bool,
+    /// Read the channel selection back after each `write_to_channel()`.
+    ///
+    /// Some bus implementations report success on a short write. When the
+    /// selection read back after the write differs from the one written,
+    /// for example after a glitch reset the device, the write returns
+    /// `Error::VerificationFailed`. This costs one control register read
+    /// per write. Defaults to `false`.
+    pub verify_channel_after_write: bool,
+}
+
+/// Per-channel configuration
+///
+/// Register it for a channel with `set_channel_config()`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelConfig {
+    /// Delay in microseconds between the channel selection and the transfer.
+    ///
+    /// This is applied by the `*_with_delay()` transfer methods, for example
+    /// to give slow devices on the channel time to settle. The delay is
+    /// applied even if the channel was already selected. Defaults to `0`,
+    /// which means no delay.
+    pub pre_transfer_delay_us: u32,
+    /// 7-bit slave addresses expected on the channel.
+    ///
+    /// Up to [`MAX_ALLOWED_ADDRESSES`](#associatedconstant.MAX_ALLOWED_ADDRESSES)
+    /// addresses can be given, for example `[Some(0x48), Some(0x49), None, None]`.
+    /// Transfers explicitly selecting the channel, like `write_to_channel()`,
+    /// `read_from()` or those of its slave I2C device, return
+    /// `Error::AddressConflict` for any other address. When several channels
+    /// are selected, the address must be allowed on one of them. This is a
+    /// debugging aid to catch wiring mistakes. Defaults to all `None`, which
+    /// allows any address.
+    pub allowed_addresses: [Option<u8>; ChannelConfig::MAX_ALLOWED_ADDRESSES],
+}
+
+impl ChannelConfig {
+    /// Maximum number of addresses in the allowlist of a channel.
+    pub const MAX_ALLOWED_ADDRESSES: usize = 4;
+
+    /// Check whether a 7-bit slave address is allowed on the channel.
+    ///
+    /// See [`allowed_addresses`](#structfield.allowed_addresses).
+    pub fn allows(&self, address: u8) -> bool {
+        self.allowed_addresses.iter().all(Option::is_none)
+            || self.allowed_addresses.contains(&Some(address))
+    }
+}
+
+/// Bus utilization statistics of a device
+///
+/// Get them with `stats()`. The counters wrap around on overflow.
+/// This requires the `metrics` feature.
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MuxStats {
+    /// Number of writes to the control register.
+    ///
+    /// Selections skipped because the channels were already selected are
+    /// not counted.
+    pub switches: u32,
+    /// Number of transfers to slaves through the device.
+    ///
+    /// A transfer is counted once its slave address is accepted, before any
+    /// channel selection it needs. Methods forwarding several transfers, like
+    /// `broadcast_write()` or `scan_channel()`, count each of them. The
+    /// closure of `with_raw()` or `run_on_channels()` counts as one transfer,
+    /// since the transfers it makes are not seen by the driver.
+    pub transfers: u32,
+}
+
+/// Size of the scratch buffer of `broadcast_write_verified()`.
+const MAX_READBACK_LEN: usize = 32;
+
+fn check_channel<E>(channel: Channel, max_channel: Channel) -> Result<Channel, Error<E>> {
+    if channel as u8 <= max_channel as u8 {
+        Ok(channel)
+    } else {
+        Err(Error::InvalidChannelMask)
+    }
+}
+
+fn check_channels<E>(channels: u8, device_mask: u8) -> Result<u8, Error<E>> {
+    if channels & !device_mask == 0 {
+        Ok(channels)
+    } else {
+        Err(Error::InvalidChannelMask)
+    }
+}
+
 #[doc(hidden)]
 #[derive(Debug, Default)]
 pub struct Xca954xaData<I2C> {
@@ -249,7 +660,154 @@ pub struct Xca954xaData<I2C> {
     pub(crate) i2c: I2C,
     /// The I²C device address.
     pub(crate) address: u8,
-    pub(crate) selected_channel_mask: u8,
+    /// Last channel mask successfully written, if known.
+    pub(crate) selected_channel_mask: Option<u8>,
+    /// Device configuration.
+    pub(crate) config: Config,
+    /// Configuration of each channel.
+    pub(crate) channel_configs: [ChannelConfig; 8],
+    /// Silicon vendor variant, if given on creation.
+    pub(crate) variant: Option<Variant>,
+    /// Bus utilization statistics.
+    #[cfg(feature = "metrics")]
+    pub(crate) stats: MuxStats,
+}
+
+impl<I2C, E> Xca954xaData<I2C>
+where
+    I2C: i2c::Read<Error = E>,
+{
+    fn read_control_register(&mut self) -> Result<u8, Error<E>> {
+        let mut data = [0];
+        self.i2c.read(self.address, &mut data).map_err(Error::I2C)?;
+        Ok(data[0])
+    }
+}
+
+impl<I2C> Xca954xaData<I2C> {
+    /// Wait for the pre-transfer delay configured for a channel, if any.
+    fn pre_transfer_delay<D: DelayUs<u32>>(&self, channel: Channel, delay: &mut D) {
+        let us = self.channel_configs[channel as usize].pre_transfer_delay_us;
+        if us != 0 {
+            delay.delay_us(us);
+        }
+    }
+
+    /// Check that a slave address does not conflict with the device address.
+    ///
+    /// If raw access is allowed, the cached channel selection is discarded.
+    fn check_address<E>(&mut self, address: impl SlaveAddress) -> Result<(), Error<E>> {
+        if address.seven_bit() == Some(self.address) {
+            if !self.config.allow_own_address {
+                return Err(Error::AddressConflict);
+            }
+            self.selected_channel_mask = None;
+        }
+        self.count_transfer();
+        Ok(())
+    }
+
+    /// Count a transfer forwarded to a slave. See `MuxStats::transfers`.
+    fn count_transfer(&mut self) {
+        #[cfg(feature = "metrics")]
+        {
+            self.stats.transfers = self.stats.transfers.wrapping_add(1);
+        }
+    }
+
+    /// Check that a slave address is allowed on at least one of the
+    /// given channels. See `ChannelConfig::allowed_addresses`.
+    ///
+    /// With no channels, only the upstream bus is reached and any address
+    /// is allowed. 10-bit addresses are never checked.
+    fn check_allowed_address<E>(
+        &self,
+        channels: u8,
+        address: impl SlaveAddress,
+    ) -> Result<(), Error<E>> {
+        let allowed = match address.seven_bit() {
+            Some(address) => {
+                channels == 0
+                    || Channel::iter_from_mask(channels)
+                        .any(|channel| self.channel_configs[channel as usize].allows(address))
+            }
+            None => true,
+        };
+        if allowed {
+            Ok(())
+        } else {
+            Err(Error::AddressConflict)
+        }
+    }
+
+    /// Same as `check_address()`, also checking that the address is allowed
+    /// on the channels about to be selected.
+    fn check_channel_address<E>(
+        &mut self,
+        channels: u8,
+        address: impl SlaveAddress,
+    ) -> Result<(), Error<E>> {
+        self.check_allowed_address(channels, address)?;
+        self.check_address(address)
+    }
+
+    /// Write the control register with the given bus operation and keep
+    /// track of the written value.
+    fn write_control_register<E>(
+        &mut self,
+        value: u8,
+        write: impl FnOnce(&mut I2C, u8, &[u8]) -> Result<(), E>,
+    ) -> Result<(), Error<E>> {
+        match write(&mut self.i2c, self.address, &[value]) {
+            Ok(()) => {
+                #[cfg(feature = "log")]
+                log::trace!(
+                    "device {:#04x}: channel selection {:?} -> {:#010b}",
+                    self.address,
+                    self.selected_channel_mask,
+                    value
+                );
+                self.selected_channel_mask = Some(value);
+                #[cfg(feature = "metrics")]
+                {
+                    self.stats.switches = self.stats.switches.wrapping_add(1);
+                }
+                Ok(())
+            }
+            Err(e) => {
+                #[cfg(feature = "log")]
+                log::warn!(
+                    "device {:#04x}: writing channel selection {:#010b} failed",
+                    self.address,
+                    value
+                );
+                self.selected_channel_mask = None;
+                Err(Error::I2C(e))
+            }
+        }
+    }
+
+    /// Same as `write_control_register()` but nothing is written if the
+    /// value is the same as the last one written.
+    fn update_control_register<E>(
+        &mut self,
+        value: u8,
+        write: impl FnOnce(&mut I2C, u8, &[u8]) -> Result<(), E>,
+    ) -> Result<(), Error<E>> {
+        if self.selected_channel_mask == Some(value) {
+            return Ok(());
+        }
+        self.write_control_register(value, write)
+    }
+}
+
+impl<I2C, E> Xca954xaData<I2C>
+where
+    I2C: i2c::Write<Error = E>,
+{
+    fn force_select_channels(&mut self, channels: u8) -> Result<(), Error<E>> {
+        self.write_control_register(channels, |i2c, address, bytes| i2c.write(address, bytes))
+    }
 }
 
 impl<I2C, E> SelectChannels for Xca954xaData<I2C>
@@ -258,11 +816,7 @@ where
 {
     type Error = Error<E>;
     fn select_channels(&mut self, channels: u8) -> Result<(), Self::Error> {
-        self.i2c
-            .write(self.address, &[channels])
-            .map_err(Error::I2C)?;
-        self.selected_channel_mask = channels;
-        Ok(())
+        self.update_control_register(channels, |i2c, address, bytes| i2c.write(address, bytes))
     }
 }
 
@@ -270,10 +824,28 @@ where
 pub trait DoOnAcquired<I2C>: private::Sealed {
     fn do_on_acquired<R, E>(
         &self,
-        f: impl FnOnce(cell::RefMut<Xca954xaData<I2C>>) -> Result<R, Error<E>>,
+        f: impl FnOnce(&mut Xca954xaData<I2C>) -> Result<R, Error<E>>,
     ) -> Result<R, Error<E>>;
 }
 
+/// Slave address types, used to tell 7-bit addresses apart.
+#[doc(hidden)]
+pub trait SlaveAddress: Copy + private::Sealed {
+    fn seven_bit(self) -> Option<u8>;
+}
+
+impl SlaveAddress for u8 {
+    fn seven_bit(self) -> Option<u8> {
+        Some(self)
+    }
+}
+
+impl SlaveAddress for u16 {
+    fn seven_bit(self) -> Option<u8> {
+        None
+    }
+}
+
 #[doc(hidden)]
 pub trait SelectChannels: private::Sealed {
     type Error;
@@ -281,240 +853,1751 @@ pub trait SelectChannels: private::Sealed {
 }
 
 /// Device driver for T/PCA9548A
-#[derive(Debug, Default)]
-pub struct Xca9548a<I2C> {
-    pub(crate) data: cell::RefCell<Xca954xaData<I2C>>,
+pub struct Xca9548a<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
 }
 
 /// Device driver for T/PCA9543A
-#[derive(Debug, Default)]
-pub struct Xca9543a<I2C> {
-    pub(crate) data: cell::RefCell<Xca954xaData<I2C>>,
+///
+/// These devices only have the A1 and A0 address pins, so their address
+/// is `0b111_00xx`. Use `SlaveAddr::Alternative(false, a1, a0)`.
+pub struct Xca9543a<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
 }
 
 /// Device driver for T/PCA9545A
-pub struct Xca9545a<I2C> {
-    pub(crate) data: cell::RefCell<Xca954xaData<I2C>>,
+pub struct Xca9545a<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
+}
+
+/// Device driver for T/PCA9546A
+pub struct Xca9546a<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
+}
+
+/// Device driver for T/PCA9544A
+///
+/// This device is a multiplexer: only one channel can be enabled at a time.
+pub struct Xca9544a<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
+}
+
+/// Device driver for PCA9542A
+///
+/// This device is a multiplexer: only one channel can be enabled at a time.
+pub struct Pca9542a<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
+}
+
+/// Device driver for PCA9540B
+///
+/// This device is a multiplexer: only one channel can be enabled at a time.
+///
+/// This device has no address pins, so its address is always the default.
+/// Use `SlaveAddr::Default`.
+pub struct Pca9540b<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
+}
+
+/// Device driver for PCA9547
+///
+/// This device is a multiplexer: only one channel can be enabled at a time.
+pub struct Pca9547<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
+}
+
+macro_rules! impl_debug {
+    ( $name:ident ) => {
+        // Only the address and the cached channel selection are printed, so
+        // that the I²C bus does not need to implement `Debug`.
+        impl<I2C, M> core::fmt::Debug for $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+        {
+            fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+                let selected_channel_mask = self
+                    .data
+                    .try_lock(|dev| dev.selected_channel_mask)
+                    .and_then(|mask| mask);
+                f.debug_struct(stringify!($name))
+                    .field("address", &self.address)
+                    .field("selected_channel_mask", &selected_channel_mask)
+                    .finish()
+            }
+        }
+    };
 }
 
 macro_rules! i2c_traits {
     ( $name:ident ) => {
-        impl<I2C> DoOnAcquired<I2C> for $name<I2C> {
+        impl<I2C, M> DoOnAcquired<I2C> for $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+        {
             fn do_on_acquired<R, E>(
                 &self,
-                f: impl FnOnce(cell::RefMut<Xca954xaData<I2C>>) -> Result<R, Error<E>>,
+                f: impl FnOnce(&mut Xca954xaData<I2C>) -> Result<R, Error<E>>,
             ) -> Result<R, Error<E>> {
-                let dev = self
-                    .data
-                    .try_borrow_mut()
-                    .map_err(|_| Error::CouldNotAcquireDevice)?;
-                f(dev)
+                self.data
+                    .lock(f)
+                    .unwrap_or(Err(Error::CouldNotAcquireDevice))
             }
         }
 
-        impl<I2C, E> i2c::Write for $name<I2C>
+        impl<I2C, M, E, A> i2c::Write<A> for $name<I2C, M>
         where
-            I2C: i2c::Write<Error = E>,
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Write<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
         {
             type Error = Error<E>;
 
-            fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
-                self.do_on_acquired(|mut dev| dev.i2c.write(address, bytes).map_err(Error::I2C))
+            fn write(&mut self, address: A, bytes: &[u8]) -> Result<(), Self::Error> {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
+                    dev.i2c.write(address, bytes).map_err(Error::I2C)
+                })
             }
         }
 
-        impl<I2C, E> i2c::Read for $name<I2C>
+        impl<I2C, M, E, A> i2c::Read<A> for $name<I2C, M>
         where
-            I2C: i2c::Read<Error = E>,
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Read<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
         {
             type Error = Error<E>;
 
-            fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
-                self.do_on_acquired(|mut dev| dev.i2c.read(address, buffer).map_err(Error::I2C))
+            fn read(&mut self, address: A, buffer: &mut [u8]) -> Result<(), Self::Error> {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
+                    dev.i2c.read(address, buffer).map_err(Error::I2C)
+                })
             }
         }
 
-        impl<I2C, E> i2c::WriteRead for $name<I2C>
+        impl<I2C, M, E, A> i2c::WriteRead<A> for $name<I2C, M>
         where
-            I2C: i2c::WriteRead<Error = E>,
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::WriteRead<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
         {
             type Error = Error<E>;
 
             fn write_read(
                 &mut self,
-                address: u8,
+                address: A,
                 bytes: &[u8],
                 buffer: &mut [u8],
             ) -> Result<(), Self::Error> {
-                self.do_on_acquired(|mut dev| {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
                     dev.i2c
                         .write_read(address, bytes, buffer)
                         .map_err(Error::I2C)
                 })
             }
         }
-    }
-}
 
-macro_rules! impl_device {
-    ( $name:ident, $parts:ident ) => {
-        impl<I2C> $name<I2C> {
-            /// Create new instance of the device
-            pub fn new(i2c: I2C, address: SlaveAddr) -> Self {
-                let data = Xca954xaData {
-                    i2c,
-                    address: address.addr(DEVICE_BASE_ADDRESS),
-                    selected_channel_mask: 0,
-                };
-                $name {
-                    data: cell::RefCell::new(data),
-                }
-            }
+        impl<I2C, M, E, A> i2c::Transactional<A> for $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Transactional<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
+        {
+            type Error = Error<E>;
 
-            /// Destroy driver instance, return I²C bus instance.
-            pub fn destroy(self) -> I2C {
-                self.data.into_inner().i2c
+            fn exec<'a>(
+                &mut self,
+                address: A,
+                operations: &mut [i2c::Operation<'a>],
+            ) -> Result<(), Self::Error> {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
+                    dev.i2c.exec(address, operations).map_err(Error::I2C)
+                })
             }
+        }
 
-            /// Split device into individual I2C devices
-            ///
-            /// It is not possible to know the compatibilities between channels
-            /// so when talking to a split I2C device, only its channel
-            /// will be selected.
-            pub fn split<'a>(&'a self) -> $parts<'a, $name<I2C>, I2C> {
-                $parts::new(&self)
+        impl<I2C, M, E, A> i2c::WriteIter<A> for $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::WriteIter<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
+        {
+            type Error = Error<E>;
+
+            fn write<B>(&mut self, address: A, bytes: B) -> Result<(), Self::Error>
+            where
+                B: IntoIterator<Item = u8>,
+            {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
+                    i2c::WriteIter::write(&mut dev.i2c, address, bytes).map_err(Error::I2C)
+                })
             }
         }
 
-    };
-    ( $name:ident, $parts:ident, no_interrupts ) => {
-        impl_device!($name, $parts);
-
-        impl<I2C, E> $name<I2C>
+        impl<I2C, M, E, A> i2c::WriteIterRead<A> for $name<I2C, M>
         where
-            I2C: i2c::Read<Error = E>,
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::WriteIterRead<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
         {
-            /// Get status of channels.
-            ///
-            /// Each bit corresponds to a channel.
-            /// Bit 0 corresponds to channel 0 and so on up to bit 7 which
-            /// corresponds to channel 7.
-            /// A `0` means the channel is disabled and a `1` that the channel is enabled.
-            pub fn get_channel_status(&mut self) -> Result<u8, Error<E>> {
-                let mut data = [0];
-                self.do_on_acquired(|mut dev| {
-                    let address = dev.address;
+            type Error = Error<E>;
+
+            fn write_iter_read<B>(
+                &mut self,
+                address: A,
+                bytes: B,
+                buffer: &mut [u8],
+            ) -> Result<(), Self::Error>
+            where
+                B: IntoIterator<Item = u8>,
+            {
+                self.do_on_acquired(|dev| {
+                    dev.check_address(address)?;
                     dev.i2c
-                        .read(address, &mut data)
+                        .write_iter_read(address, bytes, buffer)
                         .map_err(Error::I2C)
-                        .and(Ok(data[0]))
                 })
             }
         }
+    };
+}
 
+macro_rules! impl_common {
+    ( $name:ident ) => {
+        impl_debug!($name);
 
-        impl<I2C, E> $name<I2C>
+        impl<I2C, M> Default for $name<I2C, M>
         where
-            I2C: i2c::Write<Error = E>,
+            I2C: Default,
+            M: DeviceMutex<Xca954xaData<I2C>>,
         {
-            /// Select which channels are enabled.
-            ///
-            /// Each bit corresponds to a channel.
-            /// Bit 0 corresponds to channel 0 and so on up to bit 7 which
-            /// corresponds to channel 7.
-            /// A `0` disables the channel and a `1` enables it.
-            /// Several channels can be enabled at the same time
-            pub fn select_channels(&mut self, channels: u8) -> Result<(), Error<E>> {
-                self.do_on_acquired(|mut dev| dev.select_channels(channels))
+            /// Create new instance of the device at the default address
+            /// using a default-constructed I²C bus instance
+            fn default() -> Self {
+                Self::new_with_mutex(I2C::default(), SlaveAddr::default(), Config::default())
             }
         }
-    };
-    ( $name:ident, $parts:ident, $mask:expr, interrupts ) => {
-        impl_device!($name, $parts);
 
-        impl<I2C, E> $name<I2C>
-        where
-            I2C: i2c::Read<Error = E>,
-        {
-            /// Get status of channels.
-            ///
-            /// Each bit corresponds to a channel.
+        impl<I2C> $name<I2C> {
+            /// Create new instance of the device
+            pub fn new(i2c: I2C, address: SlaveAddr) -> Self {
+                Self::new_with_config(i2c, address, Config::default())
+            }
+
+            /// Create new instance of the device with the given configuration
+            pub fn new_with_config(i2c: I2C, address: SlaveAddr, config: Config) -> Self {
+                Self::new_with_mutex(i2c, address, config)
+            }
+        }
+
+        impl<I2C, M> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+        {
+            /// Value of the control register after power-on reset.
+            ///
+            /// All channels are disabled. The driver assumes this state on
+            /// creation, so selecting no channels first does not communicate
+            /// with the device. Call `disable_all_channels()` or `recover()`
+            /// if the device may not have been reset, for example after
+            /// a reset of the microcontroller alone.
+            pub const POR_CONTROL_REGISTER: u8 = 0x00;
+
+            /// Create new instance of the device using the given mutex type
+            /// to share it with its slave I2C devices. See [`DeviceMutex`].
+            pub fn new_with_mutex(i2c: I2C, address: SlaveAddr, config: Config) -> Self {
+                let address = address.addr(DEVICE_BASE_ADDRESS);
+                let data = Xca954xaData {
+                    i2c,
+                    address,
+                    selected_channel_mask: Some(Self::POR_CONTROL_REGISTER),
+                    config,
+                    channel_configs: Default::default(),
+                    variant: None,
+                    #[cfg(feature = "metrics")]
+                    stats: MuxStats::default(),
+                };
+                $name {
+                    data: M::create(data),
+                    address,
+                    _i2c: PhantomData,
+                }
+            }
+
+            /// Get the resolved 7-bit I²C address of the device.
+            ///
+            /// This does not communicate with the device.
+            pub fn address(&self) -> u8 {
+                self.address
+            }
+
+            /// Get the 8-bit form of the address used to write to the device,
+            /// `address() << 1`, as shown by logic analyzers.
+            ///
+            /// This does not communicate with the device.
+            pub fn write_address(&self) -> u8 {
+                self.address << 1
+            }
+
+            /// Get the 8-bit form of the address used to read from the device,
+            /// `(address() << 1) | 1`, as shown by logic analyzers.
+            ///
+            /// This does not communicate with the device.
+            pub fn read_address(&self) -> u8 {
+                (self.address << 1) | 1
+            }
+
+            /// Change the address of the device, keeping the I²C bus instance.
+            ///
+            /// Use this when the address pins were changed or an address
+            /// translator was reconfigured. The cached channel selection is
+            /// discarded since it belongs to the previous device.
+            /// This does not communicate with the device.
+            pub fn set_address(&mut self, address: SlaveAddr) {
+                let address = address.addr(DEVICE_BASE_ADDRESS);
+                let data = self.data.get_mut();
+                data.address = address;
+                data.selected_channel_mask = None;
+                self.address = address;
+            }
+
+            /// Check whether the device can be acquired right now.
+            ///
+            /// This does not communicate with the device. Returns `false` while
+            /// the device is acquired, for example by a slave I2C device in the
+            /// middle of a transfer. Operations then return
+            /// `Error::CouldNotAcquireDevice`.
+            pub fn is_acquirable(&self) -> bool {
+                self.data.try_lock(|_| ()).is_some()
+            }
+
+            /// Destroy driver instance, return I²C bus instance.
+            ///
+            /// This cannot fail since no slave I2C device can be borrowing
+            /// the device at this point.
+            pub fn destroy(self) -> I2C {
+                self.data.into_inner().i2c
+            }
+
+            /// Discard the cached driver state, such as the channel selection.
+            ///
+            /// Since this takes `&mut self`, no slave I2C device can be holding
+            /// the device. Use this after an unexpected error, for example
+            /// `Error::CouldNotAcquireDevice`, to make sure the next channel
+            /// selection is written to the device.
+            pub fn recover(&mut self) {
+                self.data.get_mut().selected_channel_mask = None;
+            }
+
+            /// Reset the device through its active-low `RESET` pin.
+            ///
+            /// The pin is driven low for 1 µs, which is above the minimum pulse
+            /// duration in the datasheet, and then driven high again.
+            /// This disables all channels and recovers the device if the bus
+            /// got stuck. The cached channel selection is discarded.
+            pub fn reset<P, D>(&mut self, reset_pin: &mut P, delay: &mut D) -> Result<(), P::Error>
+            where
+                P: OutputPin,
+                D: DelayUs<u32>,
+            {
+                reset_pin.set_low()?;
+                delay.delay_us(1);
+                reset_pin.set_high()?;
+                self.data.get_mut().selected_channel_mask = None;
+                Ok(())
+            }
+
+            /// Get the bus utilization statistics.
+            ///
+            /// This does not communicate with the device. Returns `None`
+            /// while the device is acquired by a slave I2C device.
+            /// This requires the `metrics` feature.
+            #[cfg(feature = "metrics")]
+            pub fn stats(&self) -> Option<MuxStats> {
+                self.data.try_lock(|dev| dev.stats)
+            }
+
+            /// Reset the bus utilization statistics to zero.
+            ///
+            /// This requires the `metrics` feature.
+            #[cfg(feature = "metrics")]
+            pub fn reset_stats(&mut self) {
+                self.data.get_mut().stats = MuxStats::default();
+            }
+        }
+
+        impl<I2C, M> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Write,
+        {
+            /// Wrap the device so that all channels are disabled when it is
+            /// dropped. See [`DisableOnDrop`].
+            pub fn disable_on_drop(self) -> DisableOnDrop<Self, I2C> {
+                DisableOnDrop::new(self)
+            }
+        }
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Read<Error = E>,
+        {
+            /// Read the raw control register byte.
+            ///
+            /// The byte is returned untouched, including any interrupt and
+            /// enable bits, so that device-specific parsing can be built on top.
+            pub fn read_control_register(&self) -> Result<u8, Error<E>> {
+                self.do_on_acquired(|dev| dev.read_control_register())
+            }
+
+            /// Check whether the device acknowledges a control register read.
+            ///
+            /// This is a lightweight presence check, for example at startup.
+            /// `embedded-hal` 0.2 bus errors cannot be told apart, so any bus
+            /// error, a NACK included, returns `Ok(false)`. Other errors, like
+            /// `Error::CouldNotAcquireDevice`, are returned as is.
+            pub fn is_connected(&self) -> Result<bool, Error<E>> {
+                match self.read_control_register() {
+                    Ok(_) => Ok(true),
+                    Err(Error::I2C(_)) => Ok(false),
+                    Err(error) => Err(error),
+                }
+            }
+        }
+    };
+}
+
+/// Control register patterns written and read back by `self_test()`.
+const SELF_TEST_PATTERNS: [u8; 4] = [0x00, 0xff, 0xaa, 0x55];
+
+/// Time the downstream buses are left idle in `reset_bus_via_mux()`, the
+/// maximum SMBus `tTIMEOUT` of 35 ms.
+const SMBUS_TIMEOUT_US: u32 = 35_000;
+
+/// Interval between reads of the interrupt pin in `wait_for_interrupt()`.
+#[cfg(feature = "unproven")]
+const INTERRUPT_POLL_US: u32 = 10;
+
+macro_rules! impl_wait_for_interrupt {
+    ( $name:ident ) => {
+        #[cfg(feature = "unproven")]
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Read<Error = E>,
+        {
+            /// Wait for an interrupt and get the status of channel interrupts.
+            ///
+            /// The `INT` pin of the device is active LOW: it is driven low
+            /// while any channel interrupt is asserted. The pin is read every
+            /// 10 µs until it is low, and then the status is read as in
+            /// `get_interrupt_status()`. Returns `None` if the pin is still high
+            /// after `timeout_us` microseconds.
+            ///
+            /// Errors reading the pin are returned as `Error::InterruptPin`.
+            ///
+            /// This requires the `unproven` feature.
+            pub fn wait_for_interrupt<P, D>(
+                &mut self,
+                int_pin: &mut P,
+                delay: &mut D,
+                timeout_us: u32,
+            ) -> Result<Option<u8>, Error<E>>
+            where
+                P: InputPin,
+                D: DelayUs<u32>,
+            {
+                let mut waited_us = 0;
+                loop {
+                    if int_pin.is_low().map_err(|_| Error::InterruptPin)? {
+                        return self.get_interrupt_status().map(Some);
+                    }
+                    if waited_us >= timeout_us {
+                        return Ok(None);
+                    }
+                    delay.delay_us(INTERRUPT_POLL_US);
+                    waited_us = waited_us.saturating_add(INTERRUPT_POLL_US);
+                }
+            }
+        }
+    };
+}
+
+macro_rules! impl_device {
+    ( $name:ident, $parts:ident, $owned:ident, $reg:ident, $max:ident ) => {
+        impl_common!($name);
+
+        impl<I2C, E> $name<I2C>
+        where
+            I2C: i2c::Write<Error = E>,
+        {
+            /// Create new instance of the device with some channels selected.
+            ///
+            /// This always performs one bus transaction to write the channel
+            /// selection, even when no channels are selected.
+            /// If it fails, the I²C bus instance is dropped.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn new_with_channels(
+                i2c: I2C,
+                address: SlaveAddr,
+                channels: impl Into<Channels>,
+            ) -> Result<Self, Error<E>> {
+                let mut device = Self::new(i2c, address);
+                device.force_select_channels(channels)?;
+                Ok(device)
+            }
+        }
+
+        impl<I2C, M> $name<I2C, M> {
+            /// Highest channel of the device.
+            ///
+            /// Selecting a channel above it returns `Error::InvalidChannelMask`.
+            pub const MAX_CHANNEL: Channel = Channel::$max;
+
+            /// Mask of all the channels of the device, `0xFF` for the
+            /// TCA9548A/PCA9548A.
+            ///
+            /// See [`select_all_channels()`](#method.select_all_channels).
+            pub const ALL_CHANNELS: u8 = $reg::CHANNELS;
+
+            /// Maximum length of the data read back by
+            /// [`broadcast_write_verified()`](#method.broadcast_write_verified).
+            pub const MAX_READBACK_LEN: usize = MAX_READBACK_LEN;
+
+            /// Split device into individual I2C devices
+            ///
+            /// It is not possible to know the compatibilities between channels
+            /// so when talking to a split I2C device, only its channel
+            /// will be selected.
+            ///
+            /// The slave I2C devices never panic when the device is already
+            /// acquired, for example by another slave I2C device used from an
+            /// interrupt. They return `Error::CouldNotAcquireDevice` instead.
+            pub fn split<'a>(&'a self) -> $parts<'a, $name<I2C, M>, I2C> {
+                $parts::new(&self)
+            }
+        }
+
+        #[cfg(feature = "alloc")]
+        impl<I2C, M> $name<I2C, M> {
+            /// Split device into individual I2C devices owning the device
+            ///
+            /// Unlike `split()`, this consumes the device, so the slave I2C
+            /// devices do not borrow it and can be moved and stored freely.
+            /// They still share the device through its mutex and a reference
+            /// count, so each transfer acquires the device just like with
+            /// `split()`. Get the device back from the last one with
+            /// `OwnedI2cSlave::into_device()`.
+            ///
+            /// This requires the `alloc` feature.
+            pub fn into_channels(self) -> $owned<$name<I2C, M>, I2C> {
+                $owned::new(self)
+            }
+        }
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Read<Error = E>,
+        {
+            /// Get status of channels.
+            ///
+            /// Each bit corresponds to a channel.
             /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
             /// A `0` means the channel is disabled and a `1` that the channel is enabled.
-            pub fn get_channel_status(&mut self) -> Result<u8, Error<E>> {
-                let mut data = [0];
-                self.do_on_acquired(|mut dev| {
-                    let address = dev.address;
+            ///
+            /// This only reads from the device and never writes to it, even if
+            /// the last selection written is unknown, and the cached selection
+            /// is left as it is. This makes it suitable for read-only health
+            /// checks. Since this takes `&self`, the status can also be polled
+            /// while the device is split.
+            pub fn get_channel_status(&self) -> Result<u8, Error<E>> {
+                Ok($reg::decode(self.read_control_register()?))
+            }
+
+            /// Read the current channel selection without disturbing it.
+            ///
+            /// Same as [`get_channel_status()`](#method.get_channel_status):
+            /// this is guaranteed to only read from the device, never to
+            /// write to it, so it is side-effect free and suitable for
+            /// read-only health checks.
+            pub fn probe(&mut self) -> Result<u8, Error<E>> {
+                self.get_channel_status()
+            }
+
+            /// Check whether a channel is enabled by reading the device.
+            ///
+            /// Checking a channel that does not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn is_channel_enabled(&self, channel: Channel) -> Result<bool, Error<E>> {
+                let mask = check_channels(channel.mask(), $reg::CHANNELS)?;
+                Ok(self.get_channel_status()? & mask != 0)
+            }
+
+            /// Compare the channels enabled in the device with the desired ones.
+            ///
+            /// Returns the `(to_enable, to_disable)` channel masks, so that a
+            /// reconciliation loop can make only the changes needed. Both are
+            /// `0` if the device is already in the desired state. This reads
+            /// the device as `get_channel_status()` does and does not write to
+            /// it.
+            ///
+            /// Desired channels that do not exist for the specific device
+            /// return `Error::InvalidChannelMask`.
+            pub fn channel_delta(
+                &self,
+                desired: impl Into<Channels>,
+            ) -> Result<(u8, u8), Error<E>> {
+                let desired = check_channels(desired.into().bits(), $reg::CHANNELS)?;
+                let current = self.get_channel_status()?;
+                Ok((desired & !current, current & !desired))
+            }
+        }
+
+        impl<I2C, M> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+        {
+            /// Get the last channel selection written to the device, if known.
+            ///
+            /// This does not communicate with the device. Returns
+            /// `Some(POR_CONTROL_REGISTER)` before the first selection and
+            /// `None` after `recover()` or `reset()`,
+            /// after a failed write or while the device is acquired by a
+            /// slave I2C device. Use `get_channel_status()` to read the
+            /// actual status from the device.
+            pub fn cached_channel_status(&self) -> Option<u8> {
+                self.data
+                    .try_lock(|dev| dev.selected_channel_mask)
+                    .and_then(|mask| mask)
+            }
+
+            /// Check whether a channel is enabled in the cached channel selection.
+            ///
+            /// This does not communicate with the device. Returns `None` when
+            /// `cached_channel_status()` does. A channel that does not exist
+            /// for the specific device is never enabled.
+            pub fn is_channel_enabled_cached(&self, channel: Channel) -> Option<bool> {
+                self.cached_channel_status()
+                    .map(|mask| mask & channel.mask() != 0)
+            }
+        }
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Write<Error = E>,
+        {
+            /// Select which channels are enabled.
+            ///
+            /// Each bit corresponds to a channel.
+            /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
+            /// A `0` disables the channel and a `1` enables it.
+            /// Several channels can be enabled at the same time.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            ///
+            /// The last successfully written selection is remembered and
+            /// nothing is sent if the requested selection is the same.
+            /// See [`force_select_channels()`](#method.force_select_channels).
+            pub fn select_channels(
+                &mut self,
+                channels: impl Into<Channels>,
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| dev.select_channels(channels))
+            }
+
+            /// Select which channels are enabled, always writing to the device.
+            ///
+            /// Same as [`select_channels()`](#method.select_channels) but the
+            /// selection is sent even if it matches the last one written.
+            /// This is useful to recover after a bus glitch.
+            pub fn force_select_channels(
+                &mut self,
+                channels: impl Into<Channels>,
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| dev.force_select_channels(channels))
+            }
+
+            /// Select a single channel, disabling all others.
+            ///
+            /// Selecting a channel that does not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn select_channel(&mut self, channel: Channel) -> Result<(), Error<E>> {
+                let channel = check_channel(channel, Self::MAX_CHANNEL)?;
+                self.select_channels(channel.mask())
+            }
+
+            /// Select a single channel by its number, disabling all others.
+            ///
+            /// Selecting a channel number above `MAX_CHANNEL` returns
+            /// `Error::InvalidChannelMask`.
+            pub fn select_channel_index(&mut self, index: usize) -> Result<(), Error<E>> {
+                match Channel::from_index(index as u8) {
+                    Some(channel) if index <= Self::MAX_CHANNEL as usize => {
+                        self.select_channel(channel)
+                    }
+                    _ => Err(Error::InvalidChannelMask),
+                }
+            }
+
+            /// Select a set of channels, disabling all others.
+            ///
+            /// The channels are combined and written at once.
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn select_channel_set(&mut self, channels: &[Channel]) -> Result<(), Error<E>> {
+                self.select_channels(mask_from_channels(channels))
+            }
+
+            /// Select which channels are enabled and wait for the switches
+            /// to settle.
+            ///
+            /// Same as [`select_channels()`](#method.select_channels) followed
+            /// by a delay of `us` microseconds. Some slow slaves do not
+            /// acknowledge when addressed right after switching, before the
+            /// switching time given in the datasheet has passed.
+            pub fn select_channels_with_delay<D: DelayUs<u32>>(
+                &mut self,
+                channels: impl Into<Channels>,
+                delay: &mut D,
+                us: u32,
+            ) -> Result<(), Error<E>> {
+                self.select_channels(channels)?;
+                delay.delay_us(us);
+                Ok(())
+            }
+
+            /// Select which channels are enabled, retrying failed writes.
+            ///
+            /// If writing the selection fails with `Error::I2C`, the write is
+            /// retried up to `retries` times. The last error is returned if
+            /// all attempts fail.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn select_channels_retry(
+                &mut self,
+                channels: impl Into<Channels>,
+                retries: u8,
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    let mut result = dev.select_channels(channels);
+                    for _ in 0..retries {
+                        match result {
+                            Err(Error::I2C(_)) => result = dev.select_channels(channels),
+                            _ => break,
+                        }
+                    }
+                    result
+                })
+            }
+
+            /// Select which channels are enabled, retrying while the device
+            /// cannot be acquired.
+            ///
+            /// If the device is in use, for example by a slave I2C device in
+            /// another task, `wait` is called and acquiring it is retried up
+            /// to `retries` times before `Error::CouldNotAcquireDevice` is
+            /// returned. `wait` can yield to the other tasks or wait a bit.
+            /// Since this takes `&self`, it can be used while the device is
+            /// split.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn select_channels_blocking(
+                &self,
+                channels: impl Into<Channels>,
+                retries: u8,
+                mut wait: impl FnMut(),
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                let mut result = self.do_on_acquired(|dev| dev.select_channels(channels));
+                for _ in 0..retries {
+                    match result {
+                        Err(Error::CouldNotAcquireDevice) => {
+                            wait();
+                            result = self.do_on_acquired(|dev| dev.select_channels(channels));
+                        }
+                        _ => break,
+                    }
+                }
+                result
+            }
+
+            /// Disable all channels, isolating all downstream buses.
+            ///
+            /// This always writes to the device, regardless of the last
+            /// selection written.
+            pub fn disable_all_channels(&mut self) -> Result<(), Error<E>> {
+                self.do_on_acquired(|dev| dev.force_select_channels(0))
+            }
+
+            /// Enable all channels at once.
+            ///
+            /// This is the counterpart of `disable_all_channels()` and always
+            /// writes `ALL_CHANNELS` to the device. All downstream buses are
+            /// then connected together, which re-introduces the address
+            /// conflicts between identical slaves the device is meant to
+            /// avoid. This is mainly useful for broadcast writes, for example a
+            /// global reset of identical slaves. Reading in this state is
+            /// unreliable.
+            pub fn select_all_channels(&mut self) -> Result<(), Error<E>> {
+                self.do_on_acquired(|dev| dev.force_select_channels(Self::ALL_CHANNELS))
+            }
+
+            /// Set the configuration of a channel. See [`ChannelConfig`].
+            ///
+            /// This does not communicate with the device.
+            /// Configuring a channel that does not exist for the specific
+            /// device returns `Error::InvalidChannelMask`.
+            pub fn set_channel_config(
+                &mut self,
+                channel: Channel,
+                config: ChannelConfig,
+            ) -> Result<(), Error<E>> {
+                check_channels(channel.mask(), $reg::CHANNELS)?;
+                self.data.get_mut().channel_configs[channel as usize] = config;
+                Ok(())
+            }
+
+            /// Select a single channel, wait for its pre-transfer delay and
+            /// write to a slave connected to it.
+            ///
+            /// The delay is configured with `set_channel_config()`.
+            /// Otherwise, this is the same as `write_to()`.
+            pub fn write_to_with_delay<D: DelayUs<u32>>(
+                &mut self,
+                channel: Channel,
+                address: u8,
+                bytes: &[u8],
+                delay: &mut D,
+            ) -> Result<(), Error<E>> {
+                let mask = check_channels(channel.mask(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.check_channel_address(mask, address)?;
+                    dev.select_channels(mask)?;
+                    dev.pre_transfer_delay(channel, delay);
+                    dev.i2c.write(address, bytes).map_err(Error::I2C)
+                })
+            }
+
+            /// Write the same data to a slave on each of some channels.
+            ///
+            /// The channels are selected one at a time, in ascending order,
+            /// and the data is written after each selection. This is one
+            /// selection and one write per channel, not a hardware broadcast.
+            /// The device is acquired only once for all transactions.
+            /// The last channel stays selected afterwards.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn broadcast_write(
+                &mut self,
+                channels: impl Into<Channels>,
+                address: u8,
+                bytes: &[u8],
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    for channel in Channel::iter_from_mask(channels) {
+                        dev.check_allowed_address(channel.mask(), address)?;
+                    }
+                    for channel in Channel::iter_from_mask(channels) {
+                        dev.check_address(address)?;
+                        dev.select_channels(channel.mask())?;
+                        dev.i2c.write(address, bytes).map_err(Error::I2C)?;
+                    }
+                    Ok(())
+                })
+            }
+
+            /// Select some channels and run a closure with the I²C bus instance.
+            ///
+            /// The device is acquired only once for the selection and the
+            /// closure, so no slave I2C device can change the selection in
+            /// between. This gives access to the methods of the concrete bus
+            /// type. Since this takes `&self`, it can be used while the device
+            /// is split. If the closure changes the channel selection, call
+            /// `recover()` afterwards.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn with_raw<R>(
+                &self,
+                channels: impl Into<Channels>,
+                f: impl FnOnce(&mut I2C) -> R,
+            ) -> Result<R, Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.select_channels(channels)?;
+                    dev.count_transfer();
+                    Ok(f(&mut dev.i2c))
+                })
+            }
+
+            /// Select some channels and run a closure with the I²C bus instance,
+            /// returning its bus error as `Error::I2C`.
+            ///
+            /// This works like `with_raw()` but merges the bus error of the
+            /// closure into the returned error, so that several transfers to
+            /// slaves on the selected channels can be batched with `?`.
+            /// If the closure changes the channel selection, call `recover()`
+            /// afterwards.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn run_on_channels<R>(
+                &mut self,
+                channels: impl Into<Channels>,
+                f: impl FnOnce(&mut I2C) -> Result<R, E>,
+            ) -> Result<R, Error<E>> {
+                self.with_raw(channels, f)?.map_err(Error::I2C)
+            }
+        }
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+        {
+            /// Select some channels and write to a slave connected to them.
+            ///
+            /// The device is acquired only once for both transactions, so no
+            /// slave I2C device can change the selection in between.
+            /// With `Config::verify_channel_after_write`, the selection is
+            /// read back after the write and `Error::VerificationFailed` is
+            /// returned if it changed.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn write_to_channel(
+                &mut self,
+                channels: impl Into<Channels>,
+                address: u8,
+                bytes: &[u8],
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.check_channel_address(channels, address)?;
+                    dev.select_channels(channels)?;
+                    dev.i2c.write(address, bytes).map_err(Error::I2C)?;
+                    if !dev.config.verify_channel_after_write {
+                        return Ok(());
+                    }
+                    let actual = $reg::decode(dev.read_control_register()?);
+                    if actual == channels {
+                        Ok(())
+                    } else {
+                        dev.selected_channel_mask = None;
+                        Err(Error::VerificationFailed {
+                            expected: channels,
+                            actual,
+                        })
+                    }
+                })
+            }
+
+            /// Disable all channels and write to a slave on the upstream bus.
+            ///
+            /// With all channels disabled, only the devices connected to the
+            /// upstream bus next to this device can respond. This is the same
+            /// as `write_to_channel()` with no channels.
+            pub fn write_upstream(&mut self, address: u8, bytes: &[u8]) -> Result<(), Error<E>> {
+                self.write_to_channel(0, address, bytes)
+            }
+
+            /// Select a single channel and write to a slave connected to it.
+            ///
+            /// This is useful to talk to identical slaves sharing the same
+            /// address on different channels. See `write_to_channel()`.
+            pub fn write_to(
+                &mut self,
+                channel: Channel,
+                address: u8,
+                bytes: &[u8],
+            ) -> Result<(), Error<E>> {
+                self.write_to_channel(channel.mask(), address, bytes)
+            }
+
+            /// Select some channels and read from a slave connected to them.
+            ///
+            /// The device is acquired only once for both transactions, so no
+            /// slave I2C device can change the selection in between.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn read_from_channel(
+                &mut self,
+                channels: impl Into<Channels>,
+                address: u8,
+                buffer: &mut [u8],
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.check_channel_address(channels, address)?;
+                    dev.select_channels(channels)?;
+                    dev.i2c.read(address, buffer).map_err(Error::I2C)
+                })
+            }
+
+            /// Select some channels and send an SMBus quick command to a slave
+            /// connected to them.
+            ///
+            /// The quick command is a zero-length read if `read` is `true` and
+            /// a zero-length write otherwise. This is often used to detect
+            /// whether SMBus devices are present. The bus implementation must
+            /// support zero-length transfers.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn quick_command(
+                &mut self,
+                channels: impl Into<Channels>,
+                address: u8,
+                read: bool,
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.check_channel_address(channels, address)?;
+                    dev.select_channels(channels)?;
+                    if read {
+                        dev.i2c.read(address, &mut []).map_err(Error::I2C)
+                    } else {
+                        dev.i2c.write(address, &[]).map_err(Error::I2C)
+                    }
+                })
+            }
+
+            /// Select some channels and read from several slaves connected to
+            /// them.
+            ///
+            /// The channels are selected once and each `(address, buffer)`
+            /// read is then performed in order while the device is acquired,
+            /// which avoids a channel switch per slave on busy channels.
+            /// With no reads, this only selects the channels. The first error
+            /// stops the remaining reads.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn read_many(
+                &mut self,
+                channels: impl Into<Channels>,
+                reads: &mut [(u8, &mut [u8])],
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    for (address, _) in reads.iter() {
+                        dev.check_channel_address(channels, *address)?;
+                    }
+                    dev.select_channels(channels)?;
+                    for (address, buffer) in reads.iter_mut() {
+                        dev.i2c.read(*address, buffer).map_err(Error::I2C)?;
+                    }
+                    Ok(())
+                })
+            }
+
+            /// Disable all channels and read from a slave on the upstream bus.
+            ///
+            /// With all channels disabled, only the devices connected to the
+            /// upstream bus next to this device can respond. This is the same
+            /// as `read_from_channel()` with no channels.
+            pub fn read_upstream(
+                &mut self,
+                address: u8,
+                buffer: &mut [u8],
+            ) -> Result<(), Error<E>> {
+                self.read_from_channel(0, address, buffer)
+            }
+
+            /// Select a single channel and read from a slave connected to it.
+            ///
+            /// This is useful to talk to identical slaves sharing the same
+            /// address on different channels. See `read_from_channel()`.
+            pub fn read_from(
+                &mut self,
+                channel: Channel,
+                address: u8,
+                buffer: &mut [u8],
+            ) -> Result<(), Error<E>> {
+                self.read_from_channel(channel.mask(), address, buffer)
+            }
+
+            /// Select a single channel, wait for its pre-transfer delay and
+            /// read from a slave connected to it.
+            ///
+            /// The delay is configured with `set_channel_config()`.
+            /// Otherwise, this is the same as `read_from()`.
+            pub fn read_from_with_delay<D: DelayUs<u32>>(
+                &mut self,
+                channel: Channel,
+                address: u8,
+                buffer: &mut [u8],
+                delay: &mut D,
+            ) -> Result<(), Error<E>> {
+                let mask = check_channels(channel.mask(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.check_channel_address(mask, address)?;
+                    dev.select_channels(mask)?;
+                    dev.pre_transfer_delay(channel, delay);
+                    dev.i2c.read(address, buffer).map_err(Error::I2C)
+                })
+            }
+        }
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+        {
+            /// Select some channels and write to and then read from a slave
+            /// connected to them.
+            ///
+            /// The device is acquired only once for both transactions, so no
+            /// slave I2C device can change the selection in between.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn write_read_on_channel(
+                &mut self,
+                channels: impl Into<Channels>,
+                address: u8,
+                bytes: &[u8],
+                buffer: &mut [u8],
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.check_channel_address(channels, address)?;
+                    dev.select_channels(channels)?;
                     dev.i2c
-                        .read(address, &mut data)
+                        .write_read(address, bytes, buffer)
                         .map_err(Error::I2C)
-                        .and(Ok(data[0] & $mask))
                 })
             }
 
-            /// Get status of channel interrupts.
+            /// Select a single channel and write to and then read from a
+            /// slave connected to it.
             ///
-            /// Each bit corresponds to a channel.
-            /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
-            /// A `1` means the channel's interrupt is high and a `0` that the channel's interrupt is low.
-            /// Note: I2C interrupts are usually active LOW!
-            pub fn get_interrupt_status(&mut self) -> Result<u8, Error<E>> {
-                let mut data = [0];
-                self.do_on_acquired(|mut dev| {
-                    let address = dev.address;
+            /// This is useful to talk to identical slaves sharing the same
+            /// address on different channels. See `write_read_on_channel()`.
+            pub fn write_read_from(
+                &mut self,
+                channel: Channel,
+                address: u8,
+                bytes: &[u8],
+                buffer: &mut [u8],
+            ) -> Result<(), Error<E>> {
+                self.write_read_on_channel(channel.mask(), address, bytes, buffer)
+            }
+
+            /// Select a single channel, wait for its pre-transfer delay and
+            /// write to and then read from a slave connected to it.
+            ///
+            /// The delay is configured with `set_channel_config()`.
+            /// Otherwise, this is the same as `write_read_from()`.
+            pub fn write_read_from_with_delay<D: DelayUs<u32>>(
+                &mut self,
+                channel: Channel,
+                address: u8,
+                bytes: &[u8],
+                buffer: &mut [u8],
+                delay: &mut D,
+            ) -> Result<(), Error<E>> {
+                let mask = check_channels(channel.mask(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.check_channel_address(mask, address)?;
+                    dev.select_channels(mask)?;
+                    dev.pre_transfer_delay(channel, delay);
                     dev.i2c
-                        .read(address, &mut data)
+                        .write_read(address, bytes, buffer)
                         .map_err(Error::I2C)
-                        .and(Ok((data[0] >> 4) & $mask))
+                })
+            }
+
+            /// Write the same data to a slave on each of some channels and
+            /// verify that each of them took it.
+            ///
+            /// This works like `broadcast_write()`, but after the write on
+            /// each channel the `readback_reg` register of the slave is read
+            /// back and compared with `expected`. The returned mask has the
+            /// bit of each channel set where the verification passed. A bus
+            /// error on the write or the readback of a slave only fails its
+            /// channel, while failing to select a channel is returned as an
+            /// error. The last channel stays selected afterwards.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`. An `expected` longer than
+            /// `MAX_READBACK_LEN` returns `Error::BufferTooLong` before any
+            /// transfer.
+            pub fn broadcast_write_verified(
+                &mut self,
+                channels: impl Into<Channels>,
+                address: u8,
+                bytes: &[u8],
+                readback_reg: u8,
+                expected: &[u8],
+            ) -> Result<u8, Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                if expected.len() > Self::MAX_READBACK_LEN {
+                    return Err(Error::BufferTooLong);
+                }
+                self.do_on_acquired(|dev| {
+                    for channel in Channel::iter_from_mask(channels) {
+                        dev.check_allowed_address(channel.mask(), address)?;
+                    }
+                    let mut passed = 0;
+                    for channel in Channel::iter_from_mask(channels) {
+                        dev.check_address(address)?;
+                        dev.select_channels(channel.mask())?;
+                        if dev.i2c.write(address, bytes).is_err() {
+                            continue;
+                        }
+                        dev.count_transfer();
+                        let mut scratch = [0; MAX_READBACK_LEN];
+                        let readback = &mut scratch[..expected.len()];
+                        let verified = dev
+                            .i2c
+                            .write_read(address, &[readback_reg], readback)
+                            .is_ok()
+                            && readback == expected;
+                        if verified {
+                            passed |= channel.mask();
+                        }
+                    }
+                    Ok(passed)
                 })
             }
         }
 
-        impl<I2C, E> $name<I2C>
+        impl<I2C, M, E> $name<I2C, M>
         where
-            I2C: i2c::Write<Error = E>,
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Read<Error = E> + i2c::Write<Error = E>,
         {
-            /// Select which channels are enabled.
+            /// Enable a channel, keeping the rest of the channels as they are.
+            ///
+            /// This reads the current channel selection and writes it back with
+            /// the channel enabled, so it performs two bus transactions. The write
+            /// is always sent, even if the last selection written is the same.
+            ///
+            /// Enabling a channel that does not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn enable_channel(&mut self, channel: Channel) -> Result<(), Error<E>> {
+                let mask = check_channels(channel.mask(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    let current = dev.read_control_register()?;
+                    dev.force_select_channels($reg::decode(current) | mask)
+                })
+            }
+
+            /// Enable some channels one at a time in the given order, keeping
+            /// the rest of the channels as they are.
+            ///
+            /// For each channel, the current channel selection is read and
+            /// written back with the channel enabled, so the last channel is
+            /// the last one connected. This is slower than selecting all the
+            /// channels with a single write, but the order is deterministic,
+            /// for example to connect the highest-priority bus last. Channels
+            /// already enabled are not written again. The device is acquired
+            /// only once for the whole sequence.
+            ///
+            /// Enabling channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask` before anything is written.
+            pub fn select_channels_ordered(
+                &mut self,
+                channels: &[Channel],
+            ) -> Result<(), Error<E>> {
+                check_channels(mask_from_channels(channels), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    for channel in channels {
+                        let current = $reg::decode(dev.read_control_register()?);
+                        let next = current | channel.mask();
+                        if next != current {
+                            dev.force_select_channels(next)?;
+                        }
+                    }
+                    Ok(())
+                })
+            }
+
+            /// Enable some channels if any of them is disabled, keeping the rest
+            /// of the channels as they are.
+            ///
+            /// This reads the current channel selection from the device instead
+            /// of relying on the last selection written, and writes only if some
+            /// of the channels are disabled. Returns whether a write occurred.
+            ///
+            /// Enabling channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn ensure_channels(
+                &mut self,
+                channels: impl Into<Channels>,
+            ) -> Result<bool, Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    let current = $reg::decode(dev.read_control_register()?);
+                    if current & channels == channels {
+                        Ok(false)
+                    } else {
+                        dev.force_select_channels(current | channels)?;
+                        Ok(true)
+                    }
+                })
+            }
+
+            /// Select which channels are enabled and verify the selection by
+            /// reading it back.
+            ///
+            /// The selection is always written, regardless of the last
+            /// selection written. If the selection read back is different,
+            /// `Error::VerificationFailed` is returned with both selections,
+            /// so the channels that could not be enabled are
+            /// `expected & !actual`.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn select_channels_verified(
+                &mut self,
+                channels: impl Into<Channels>,
+            ) -> Result<(), Error<E>> {
+                let channels = check_channels(channels.into().bits(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.force_select_channels(channels)?;
+                    let actual = $reg::decode(dev.read_control_register()?);
+                    if actual == channels {
+                        Ok(())
+                    } else {
+                        dev.selected_channel_mask = None;
+                        Err(Error::VerificationFailed {
+                            expected: channels,
+                            actual,
+                        })
+                    }
+                })
+            }
+
+            /// Check the communication with the device by writing test patterns
+            /// to the control register and reading each of them back.
+            ///
+            /// The patterns `0x00`, `0xFF`, `0xAA` and `0x55` are limited to the
+            /// channels of the specific device. All channels are disabled at the
+            /// end. If a pattern read back is different,
+            /// `Error::VerificationFailed` is returned with both patterns and the
+            /// channel selection is left unknown.
+            pub fn self_test(&mut self) -> Result<(), Error<E>> {
+                self.do_on_acquired(|dev| {
+                    for pattern in SELF_TEST_PATTERNS.iter() {
+                        let expected = pattern & $reg::CHANNELS;
+                        dev.force_select_channels(expected)?;
+                        let actual = $reg::decode(dev.read_control_register()?);
+                        if actual != expected {
+                            dev.selected_channel_mask = None;
+                            return Err(Error::VerificationFailed { expected, actual });
+                        }
+                    }
+                    dev.force_select_channels(0)
+                })
+            }
+
+            /// Temporarily select some channels while running a closure.
+            ///
+            /// The current channel selection is read, the given channels are
+            /// selected and the closure is called. Afterwards the previous
+            /// selection is restored, regardless of what the closure returned.
+            /// Both selections are always written, since the cached selection
+            /// may not match what was read.
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn with_channels<R>(
+                &mut self,
+                channels: impl Into<Channels>,
+                f: impl FnOnce(&mut Self) -> R,
+            ) -> Result<R, Error<E>> {
+                let previous = self.get_channel_status()?;
+                self.force_select_channels(channels)?;
+                let result = f(self);
+                self.force_select_channels(previous)?;
+                Ok(result)
+            }
+
+            /// Temporarily select some channels until the returned guard is
+            /// dropped.
+            ///
+            /// The current channel selection is read and the given channels are
+            /// selected. The guard can be used like the device and restores
+            /// the previous selection when dropped. Errors while restoring are
+            /// ignored. Both selections are always written. See
+            /// [`ChannelGuard`].
+            ///
+            /// Selecting channels that do not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn select_scoped(
+                &mut self,
+                channels: impl Into<Channels>,
+            ) -> Result<ChannelGuard<'_, Self, I2C>, Error<E>> {
+                let previous = self.get_channel_status()?;
+                self.force_select_channels(channels)?;
+                Ok(ChannelGuard::new(self, previous))
+            }
+
+            /// Isolate the downstream buses for an SMBus timeout, then restore
+            /// the channel selection.
+            ///
+            /// The current channel selection is read, all channels are
+            /// disabled and the bus is left idle for 35 ms, the maximum SMBus
+            /// `tTIMEOUT`. SMBus slaves stuck in a transfer reset their
+            /// interface once that timeout expires, while the other channels
+            /// and the upstream bus are unaffected. The previous selection is
+            /// then written again. The device stays acquired for the whole
+            /// sequence. Slaves without SMBus timeout support are not
+            /// recovered by this.
+            pub fn reset_bus_via_mux<D: DelayUs<u32>>(
+                &mut self,
+                delay: &mut D,
+            ) -> Result<(), Error<E>> {
+                self.do_on_acquired(|dev| {
+                    let previous = $reg::decode(dev.read_control_register()?);
+                    dev.force_select_channels(0)?;
+                    delay.delay_us(SMBUS_TIMEOUT_US);
+                    dev.force_select_channels(previous)
+                })
+            }
+
+            /// Disable a channel, keeping the rest of the channels as they are.
+            ///
+            /// This reads the current channel selection and writes it back with
+            /// the channel disabled, so it performs two bus transactions. The write
+            /// is always sent, even if the last selection written is the same.
+            ///
+            /// Disabling a channel that does not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn disable_channel(&mut self, channel: Channel) -> Result<(), Error<E>> {
+                let mask = check_channels(channel.mask(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    let current = dev.read_control_register()?;
+                    dev.force_select_channels($reg::decode(current) & !mask)
+                })
+            }
+
+            /// Toggle a channel, keeping the rest of the channels as they are.
+            ///
+            /// This reads the current channel selection and writes it back with
+            /// the channel flipped, so it performs two bus transactions. The
+            /// write is always sent, even if the last selection written is the
+            /// same. Returns whether the channel is enabled afterwards.
+            ///
+            /// Toggling a channel that does not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn toggle_channel(&mut self, channel: Channel) -> Result<bool, Error<E>> {
+                let mask = check_channels(channel.mask(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    let toggled = $reg::decode(dev.read_control_register()?) ^ mask;
+                    dev.force_select_channels(toggled)?;
+                    Ok(toggled & mask != 0)
+                })
+            }
+
+            /// Scan the bus behind a channel for slaves.
+            ///
+            /// The channel is selected and a zero-length read is attempted at
+            /// each 7-bit address. The returned array is indexed by address and
+            /// contains `true` for each address that acknowledged.
+            /// The address of the device itself is skipped.
+            ///
+            /// Scanning a channel that does not exist for the specific device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn scan_channel(&mut self, channel: Channel) -> Result<[bool; 128], Error<E>> {
+                let mask = check_channels(channel.mask(), $reg::CHANNELS)?;
+                self.do_on_acquired(|dev| {
+                    dev.select_channels(mask)?;
+                    let mut found = [false; 128];
+                    for (address, found) in (0..).zip(found.iter_mut()) {
+                        if address != dev.address {
+                            dev.count_transfer();
+                            *found = dev.i2c.read(address, &mut []).is_ok();
+                        }
+                    }
+                    Ok(found)
+                })
+            }
+        }
+    };
+    ( $name:ident, $parts:ident, $owned:ident, $reg:ident, $max:ident, interrupts ) => {
+        impl_device!($name, $parts, $owned, $reg, $max);
+        impl_wait_for_interrupt!($name);
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Read<Error = E>,
+        {
+            /// Get status of channel interrupts.
             ///
             /// Each bit corresponds to a channel.
             /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
-            /// A `0` disables the channel and a `1` enables it.
-            /// Several channels can be enabled at the same time.
+            /// A `1` means the channel's interrupt is high and a `0` that the channel's interrupt is low.
+            /// Note: I2C interrupts are usually active LOW!
+            pub fn get_interrupt_status(&self) -> Result<u8, Error<E>> {
+                Ok((self.read_control_register()? >> 4) & $reg::CHANNELS)
+            }
+
+            /// Get the enabled channels and the interrupt status at once.
             ///
-            /// Channels/bits that does not exist for the specific device are ignored.
-            pub fn select_channels(&mut self, channels: u8) -> Result<(), Error<E>> {
-                self.do_on_acquired(|mut dev| dev.select_channels(channels & $mask))
+            /// Both are read from a single control register read and parsed
+            /// according to the layout of the device. See [`Status`].
+            pub fn get_status(&self) -> Result<Status, Error<E>> {
+                let register = self.read_control_register()?;
+                Ok(Status::new(
+                    $reg::decode(register),
+                    (register >> 4) & $reg::CHANNELS,
+                ))
             }
         }
+    };
+}
 
-    }
+macro_rules! impl_variant {
+    ( $name:ident ) => {
+        impl<I2C> $name<I2C> {
+            /// Create new instance of the TCA version of the device.
+            ///
+            /// Same as `new()`, recording `Variant::Tca`.
+            pub fn new_tca(i2c: I2C, address: SlaveAddr) -> Self {
+                Self::new_with_variant(i2c, address, Variant::Tca)
+            }
+
+            /// Create new instance of the PCA version of the device.
+            ///
+            /// Same as `new()`, recording `Variant::Pca`.
+            pub fn new_pca(i2c: I2C, address: SlaveAddr) -> Self {
+                Self::new_with_variant(i2c, address, Variant::Pca)
+            }
+
+            fn new_with_variant(i2c: I2C, address: SlaveAddr, variant: Variant) -> Self {
+                let mut device = Self::new(i2c, address);
+                device.data.get_mut().variant = Some(variant);
+                device
+            }
+        }
+
+        impl<I2C, M> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+        {
+            /// Get the silicon vendor variant given on creation.
+            ///
+            /// This does not communicate with the device. Returns `None` if
+            /// the device was not created with `new_tca()` or `new_pca()`,
+            /// or while it is acquired by a slave I2C device.
+            pub fn variant(&self) -> Option<Variant> {
+                self.data
+                    .try_lock(|dev| dev.variant)
+                    .and_then(|variant| variant)
+            }
+        }
+    };
 }
 
-impl_device!(Xca9548a, Parts, no_interrupts);
+impl_device!(Xca9548a, Parts, OwnedParts, Switch8, Ch7);
 i2c_traits!(Xca9548a);
+impl_variant!(Xca9548a);
 
-impl_device!(Xca9543a, Parts2, 0x03, interrupts);
+impl_device!(Xca9543a, Parts2, OwnedParts2, Switch2, Ch1, interrupts);
 i2c_traits!(Xca9543a);
+impl_variant!(Xca9543a);
 
-impl_device!(Xca9545a, Parts4, 0x0f, interrupts);
+impl_device!(Xca9545a, Parts4, OwnedParts4, Switch4, Ch3, interrupts);
 i2c_traits!(Xca9545a);
+impl_variant!(Xca9545a);
 
+impl_device!(Xca9546a, Parts4, OwnedParts4, Switch4, Ch3);
+i2c_traits!(Xca9546a);
+impl_variant!(Xca9546a);
 
+macro_rules! impl_mux {
+    ( $name:ident, $reg:ident, $max:ident ) => {
+        impl_common!($name);
+
+        impl<I2C, M> $name<I2C, M> {
+            /// Highest channel of the device.
+            ///
+            /// Selecting a channel above it returns `Error::InvalidChannelMask`.
+            pub const MAX_CHANNEL: Channel = Channel::$max;
+        }
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Write<Error = E>,
+        {
+            /// Select the enabled channel.
+            ///
+            /// Any previously enabled channel is disabled.
+            /// Selecting a channel that does not exist for the device
+            /// returns `Error::InvalidChannelMask`.
+            pub fn select_channel(&mut self, channel: Channel) -> Result<(), Error<E>> {
+                let register = $reg::encode(check_channel(channel, Self::MAX_CHANNEL)?.mask());
+                self.do_on_acquired(|dev| dev.select_channels(register))
+            }
+
+            /// Disable all channels.
+            ///
+            /// This always writes to the device, regardless of the last
+            /// selection written.
+            pub fn disable(&mut self) -> Result<(), Error<E>> {
+                self.do_on_acquired(|dev| dev.force_select_channels(0))
+            }
+        }
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Read<Error = E>,
+        {
+            /// Get the enabled channel, if any.
+            pub fn get_selected_channel(&self) -> Result<Option<Channel>, Error<E>> {
+                let register = self.read_control_register()?;
+                Ok(Channel::iter_from_mask($reg::decode(register)).next())
+            }
+        }
+        i2c_traits!($name);
+    };
+    ( $name:ident, $reg:ident, $max:ident, interrupts ) => {
+        impl_mux!($name, $reg, $max);
+        impl_wait_for_interrupt!($name);
+
+        impl<I2C, M, E> $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Read<Error = E>,
+        {
+            /// Get status of channel interrupts.
+            ///
+            /// Each bit corresponds to a channel.
+            /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
+            /// A `1` means the channel's interrupt is high and a `0` that the channel's interrupt is low.
+            /// Note: I2C interrupts are usually active LOW!
+            pub fn get_interrupt_status(&self) -> Result<u8, Error<E>> {
+                Ok((self.read_control_register()? >> 4) & $reg::CHANNELS)
+            }
+
+            /// Get the enabled channels and the interrupt status at once.
+            ///
+            /// Both are read from a single control register read and parsed
+            /// according to the layout of the device. See [`Status`].
+            pub fn get_status(&self) -> Result<Status, Error<E>> {
+                let register = self.read_control_register()?;
+                Ok(Status::new(
+                    $reg::decode(register),
+                    (register >> 4) & $reg::CHANNELS,
+                ))
+            }
+        }
+    };
+}
+
+impl_mux!(Xca9544a, Mux4, Ch3, interrupts);
+impl_variant!(Xca9544a);
+impl_mux!(Pca9542a, Mux2, Ch1, interrupts);
+impl_mux!(Pca9540b, Mux2, Ch1);
+impl_mux!(Pca9547, Mux8, Ch7);
+
+impl<I2C, M, E> Pca9547<I2C, M>
+where
+    M: DeviceMutex<Xca954xaData<I2C>>,
+    I2C: i2c::Write<Error = E>,
+{
+    /// Disable all channels.
+    ///
+    /// Same as [`disable()`](#method.disable).
+    pub fn disable_all(&mut self) -> Result<(), Error<E>> {
+        self.disable()
+    }
+}
+
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(feature = "alloc")]
+mod chain;
+#[cfg(feature = "alloc")]
+pub use chain::MuxChain;
+mod channel;
+pub use channel::{channels_from_mask, mask_from_channels, Channel, Channels, Status};
+#[cfg(feature = "eh1")]
+mod eh1;
+mod guard;
+pub use guard::{ChannelGuard, DisableOnDrop};
+mod ltc4306;
+pub use ltc4306::Ltc4306;
+mod mutex;
+pub use mutex::DeviceMutex;
 mod parts;
 pub use parts::{I2cSlave, Parts, Parts2, Parts4};
+#[cfg(feature = "alloc")]
+pub use parts::{OwnedI2cSlave, OwnedParts, OwnedParts2, OwnedParts4};
+
+mod register;
+mod switch;
+use register::{ControlRegister, Mux2, Mux4, Mux8, Switch2, Switch4, Switch8};
+pub use switch::I2cSwitch;
 
 mod private {
     use super::*;
 
     pub trait Sealed {}
+    impl Sealed for u8 {}
+    impl Sealed for u16 {}
     impl<I2C> Sealed for Xca954xaData<I2C> {}
-    impl<I2C> Sealed for Xca9548a<I2C> {}
-    impl<I2C> Sealed for Xca9543a<I2C> {}
-    impl<I2C> Sealed for Xca9545a<I2C> {}
+    impl<I2C, M> Sealed for Xca9548a<I2C, M> {}
+    impl<I2C, M> Sealed for Xca9543a<I2C, M> {}
+    impl<I2C, M> Sealed for Xca9545a<I2C, M> {}
+    impl<I2C, M> Sealed for Xca9546a<I2C, M> {}
+    impl<I2C, M> Sealed for Xca9544a<I2C, M> {}
+    impl<I2C, M> Sealed for Pca9542a<I2C, M> {}
+    impl<I2C, M> Sealed for Pca9540b<I2C, M> {}
+    impl<I2C, M> Sealed for Pca9547<I2C, M> {}
+    impl<I2C, M> Sealed for Ltc4306<I2C, M> {}
     impl<'a, DEV, I2C> Sealed for Parts<'a, DEV, I2C> {}
     impl<'a, DEV, I2C> Sealed for Parts2<'a, DEV, I2C> {}
     impl<'a, DEV, I2C> Sealed for Parts4<'a, DEV, I2C> {}
     impl<'a, DEV, I2C> Sealed for I2cSlave<'a, DEV, I2C> {}
+    #[cfg(feature = "alloc")]
+    impl<DEV, I2C> Sealed for OwnedParts<DEV, I2C> {}
+    #[cfg(feature = "alloc")]
+    impl<DEV, I2C> Sealed for OwnedParts2<DEV, I2C> {}
+    #[cfg(feature = "alloc")]
+    impl<DEV, I2C> Sealed for OwnedParts4<DEV, I2C> {}
+    #[cfg(feature = "alloc")]
+    impl<DEV, I2C> Sealed for OwnedI2cSlave<DEV, I2C> {}
 }
 
 #[cfg(test)]
@@ -551,4 +2634,59 @@ mod tests {
             SlaveAddr::Alternative(true, true, true).addr(BASE_ADDR)
         );
     }
+
+    #[test]
+    fn can_use_raw_address() {
+        assert_eq!(0x20, SlaveAddr::Raw(0x20).addr(BASE_ADDR));
+        assert_eq!(0x20, SlaveAddr::Raw(0xa0).addr(BASE_ADDR));
+        assert_eq!(0x20, SlaveAddr::Raw(0x20).address());
+    }
+
+    #[test]
+    fn can_create_from_address() {
+        for address in 0x70..=0x77 {
+            let addr = SlaveAddr::from_address(address).unwrap();
+            assert_eq!(address, addr.addr(BASE_ADDR));
+            assert_eq!(
+                address,
+                SlaveAddr::try_from(address).unwrap().addr(BASE_ADDR)
+            );
+        }
+    }
+
+    #[test]
+    fn can_compute_address_in_const() {
+        const DEFAULT_ADDR: u8 = SlaveAddr::Default.address();
+        const ALTERNATIVE_ADDR: u8 = SlaveAddr::Alternative(true, false, true).address();
+        assert_eq!(BASE_ADDR, DEFAULT_ADDR);
+        assert_eq!(0b111_0101, ALTERNATIVE_ADDR);
+    }
+
+    #[test]
+    fn can_compare_addresses() {
+        let addr = SlaveAddr::Alternative(false, true, false);
+        let copy = addr;
+        assert_eq!(addr, copy);
+        assert_ne!(SlaveAddr::default(), addr);
+        assert_eq!(Some(addr), SlaveAddr::from_address(0b111_0010));
+    }
+
+    #[test]
+    fn rejects_address_out_of_range() {
+        assert!(SlaveAddr::from_address(0x30).is_none());
+        assert!(SlaveAddr::from_address(0x78).is_none());
+        assert_eq!(Err(()), SlaveAddr::try_from(0x30));
+    }
+
+    #[test]
+    fn rejects_channel_out_of_range() {
+        match check_channel::<()>(Channel::Ch4, Channel::Ch3) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel not reported"),
+        }
+        match check_channel::<()>(Channel::Ch2, Channel::Ch1) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel not reported"),
+        }
+    }
 }
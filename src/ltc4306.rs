@@ -0,0 +1,164 @@
+use core::cell;
+use core::marker::PhantomData;
+use hal::blocking::i2c;
+
+use crate::{
+    check_channels, Channel, Channels, Config, DeviceMutex, DoOnAcquired, Error, SlaveAddress,
+    Xca954xaData,
+};
+
+/// Register storing the status flags
+const STATUS: u8 = 0;
+/// Register storing the bus connection (FET state) bits
+pub(crate) const CONNECTION: u8 = 3;
+
+const FAILED_CONNECTION: u8 = 0b0000_0100;
+const LATCHED_TIMEOUT: u8 = 0b0000_0010;
+pub(crate) const CHANNELS_MASK: u8 = 0x0f;
+
+/// Encode channels in the bus connection register: bus 1 (channel 0) is
+/// bit 7 down to bus 4 (channel 3) at bit 4.
+pub(crate) fn encode_channels(channels: u8) -> u8 {
+    (channels & CHANNELS_MASK).reverse_bits()
+}
+
+/// Decode the channels from the bus connection register.
+pub(crate) fn decode_channels(connection: u8) -> u8 {
+    connection.reverse_bits() & CHANNELS_MASK
+}
+
+/// Device driver for LTC4306
+///
+/// This device is a four-channel switch with a register map. The channels
+/// are enabled through the bus connection register and the stuck bus
+/// detection is reported in the status register.
+///
+/// The address is set through three three-state pins, so it is given as a
+/// raw 7-bit address. See the datasheet for the address table.
+pub struct Ltc4306<I2C, M = cell::RefCell<Xca954xaData<I2C>>> {
+    pub(crate) data: M,
+    pub(crate) address: u8,
+    pub(crate) _i2c: PhantomData<I2C>,
+}
+
+impl<I2C, M> Ltc4306<I2C, M> {
+    /// Highest channel of the device.
+    ///
+    /// Selecting a channel above it returns `Error::InvalidChannelMask`.
+    pub const MAX_CHANNEL: Channel = Channel::Ch3;
+}
+
+impl<I2C> Ltc4306<I2C> {
+    /// Create new instance of the device
+    pub fn new(i2c: I2C, address: u8) -> Self {
+        let data = Xca954xaData {
+            i2c,
+            address,
+            selected_channel_mask: None,
+            config: Config::default(),
+            channel_configs: Default::default(),
+            variant: None,
+            #[cfg(feature = "metrics")]
+            stats: Default::default(),
+        };
+        Ltc4306 {
+            data: cell::RefCell::new(data),
+            address,
+            _i2c: PhantomData,
+        }
+    }
+
+    /// Get the 7-bit I²C address of the device.
+    ///
+    /// This does not communicate with the device.
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Get the 8-bit form of the address used to write to the device,
+    /// `address() << 1`, as shown by logic analyzers.
+    ///
+    /// This does not communicate with the device.
+    pub fn write_address(&self) -> u8 {
+        self.address << 1
+    }
+
+    /// Get the 8-bit form of the address used to read from the device,
+    /// `(address() << 1) | 1`, as shown by logic analyzers.
+    ///
+    /// This does not communicate with the device.
+    pub fn read_address(&self) -> u8 {
+        (self.address << 1) | 1
+    }
+
+    /// Destroy driver instance, return I²C bus instance.
+    pub fn destroy(self) -> I2C {
+        self.data.into_inner().i2c
+    }
+}
+
+impl<I2C, M, E> Ltc4306<I2C, M>
+where
+    M: DeviceMutex<Xca954xaData<I2C>>,
+    I2C: i2c::Write<Error = E>,
+{
+    /// Select which channels are enabled.
+    ///
+    /// Bit 0 corresponds to channel 0 (bus 1 in the datasheet), bit 1 to
+    /// channel 1 and so on. Several channels can be enabled at the same time.
+    /// In the bus connection register, bus 1 is bit 7 and bus 4 is bit 4.
+    ///
+    /// Selecting channels that do not exist for the device
+    /// returns `Error::InvalidChannelMask`.
+    pub fn select_channels(&mut self, channels: impl Into<Channels>) -> Result<(), Error<E>> {
+        let channels = check_channels(channels.into().bits(), CHANNELS_MASK)?;
+        self.do_on_acquired(|dev| {
+            dev.update_control_register(encode_channels(channels), |i2c, address, value| {
+                i2c.write(address, &[CONNECTION, value[0]])
+            })
+        })
+    }
+}
+
+impl<I2C, M, E> Ltc4306<I2C, M>
+where
+    M: DeviceMutex<Xca954xaData<I2C>>,
+    I2C: i2c::WriteRead<Error = E>,
+{
+    /// Get status of channels.
+    ///
+    /// Each bit corresponds to a channel.
+    /// Bit 0 corresponds to channel 0, bit 1 to channel 1 and so on.
+    /// A `0` means the channel is disabled and a `1` that the channel is enabled.
+    pub fn get_channel_status(&self) -> Result<u8, Error<E>> {
+        let connection = self.read_register(CONNECTION)?;
+        Ok(decode_channels(connection))
+    }
+
+    /// Get the stuck bus flags as `(upstream, downstream)`.
+    ///
+    /// The upstream flag is the latched stuck bus timeout flag, set when
+    /// the bus stayed low for longer than the configured timeout.
+    /// The downstream flag is the failed connection attempt flag, set when
+    /// a channel could not be enabled because its bus was low.
+    pub fn bus_stuck(&self) -> Result<(bool, bool), Error<E>> {
+        let status = self.read_register(STATUS)?;
+        Ok((
+            status & LATCHED_TIMEOUT != 0,
+            status & FAILED_CONNECTION != 0,
+        ))
+    }
+
+    fn read_register(&self, register: u8) -> Result<u8, Error<E>> {
+        self.do_on_acquired(|dev| {
+            let mut data = [0];
+            dev.i2c
+                .write_read(dev.address, &[register], &mut data)
+                .map_err(Error::I2C)?;
+            Ok(data[0])
+        })
+    }
+}
+
+impl_debug!(Ltc4306);
+i2c_traits!(Ltc4306);
@@ -0,0 +1,93 @@
+use core::cell::RefCell;
+
+/// Interior mutability wrapper used to share a device between its slave I2C devices.
+///
+/// The devices use a `RefCell` by default, which is enough as long as the
+/// device and its slave I2C devices are only used from a single context.
+/// Implement this trait on a mutex to share them across tasks or interrupts,
+/// then create the device with `new_with_mutex()`.
+pub trait DeviceMutex<T> {
+    /// Create a new mutex containing the given value.
+    fn create(value: T) -> Self;
+
+    /// Lock the mutex and call the closure with the contained value.
+    ///
+    /// Returns `None` if the mutex could not be acquired. The driver then
+    /// returns `Error::CouldNotAcquireDevice`. The mutex is held for the
+    /// whole channel selection and transfer, so this also rejects any
+    /// re-entrant access, for example from the I2C bus implementation.
+    fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R>;
+
+    /// Lock the mutex without waiting and call the closure with the
+    /// contained value.
+    ///
+    /// Returns `None` if the mutex is held, even by the same context. This
+    /// is used by the diagnostics that do not communicate with the device,
+    /// like `is_acquirable()` and `cached_channel_status()`, so that they
+    /// never block. Defaults to `lock()`, which is enough for mutexes that
+    /// do not wait.
+    fn try_lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.lock(f)
+    }
+
+    /// Get a mutable reference to the contained value.
+    ///
+    /// No locking is needed since the mutex is mutably borrowed.
+    fn get_mut(&mut self) -> &mut T;
+
+    /// Destroy the mutex, returning the contained value.
+    fn into_inner(self) -> T;
+}
+
+impl<T> DeviceMutex<T> for RefCell<T> {
+    fn create(value: T) -> Self {
+        RefCell::new(value)
+    }
+
+    fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        self.try_borrow_mut().ok().map(|mut value| f(&mut value))
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        RefCell::get_mut(self)
+    }
+
+    fn into_inner(self) -> T {
+        RefCell::into_inner(self)
+    }
+}
+
+/// Blocking `std` mutex, to share the device across threads.
+///
+/// Locking waits for other threads to release the device. A poisoned mutex
+/// cannot be acquired, so the driver returns `Error::CouldNotAcquireDevice`
+/// after a thread panicked while using the device. Unlike with `RefCell`,
+/// re-entrant access from the same thread deadlocks instead of failing.
+/// The diagnostics using `try_lock()` never wait, so they can be used from
+/// any thread, including from inside `with_raw()`.
+#[cfg(feature = "std")]
+impl<T> DeviceMutex<T> for std::sync::Mutex<T> {
+    fn create(value: T) -> Self {
+        std::sync::Mutex::new(value)
+    }
+
+    fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        std::sync::Mutex::lock(self)
+            .ok()
+            .map(|mut value| f(&mut value))
+    }
+
+    fn try_lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+        std::sync::Mutex::try_lock(self)
+            .ok()
+            .map(|mut value| f(&mut value))
+    }
+
+    fn get_mut(&mut self) -> &mut T {
+        std::sync::Mutex::get_mut(self).unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn into_inner(self) -> T {
+        std::sync::Mutex::into_inner(self).unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
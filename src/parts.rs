@@ -1,12 +1,32 @@
-use super::{DoOnAcquired, Error, SelectChannels};
+use super::{DoOnAcquired, Error, SelectChannels, SlaveAddress};
+#[cfg(feature = "alloc")]
+use alloc::rc::Rc;
 use core::marker::PhantomData;
 use hal::blocking::i2c;
 
 /// Slave I2C device
-pub struct I2cSlave<'a, DEV: 'a, I2C>(&'a DEV, u8, PhantomData<I2C>);
+pub struct I2cSlave<'a, DEV: 'a, I2C>(pub(crate) &'a DEV, pub(crate) u8, PhantomData<I2C>);
+
+/// Slave I2C device owning a shared reference to its device
+///
+/// Unlike [`I2cSlave`], it does not borrow the device, so it can be moved
+/// and stored freely. The device is still shared through its mutex.
+/// This requires the `alloc` feature.
+#[cfg(feature = "alloc")]
+pub struct OwnedI2cSlave<DEV, I2C>(pub(crate) Rc<DEV>, pub(crate) u8, PhantomData<I2C>);
+
+#[cfg(feature = "alloc")]
+impl<DEV, I2C> OwnedI2cSlave<DEV, I2C> {
+    /// Return the device if this is the last slave I2C device sharing it.
+    ///
+    /// Otherwise the slave I2C device is dropped and `None` is returned.
+    pub fn into_device(self) -> Option<DEV> {
+        Rc::try_unwrap(self.0).ok()
+    }
+}
 
 macro_rules! parts {
-    ( $name:ident; $( $i2cx:ident, $channel:expr ),+ ) => {
+    ( $name:ident, $owned:ident; $( $i2cx:ident, $channel:expr ),+ ) => {
 
         /// Slave I2C devices
         pub struct $name<'a, DEV:'a, I2C> {
@@ -25,72 +45,172 @@ macro_rules! parts {
                 }
             }
         }
+
+        /// Slave I2C devices owning a shared reference to the device
+        ///
+        /// This requires the `alloc` feature.
+        #[cfg(feature = "alloc")]
+        pub struct $owned<DEV, I2C> {
+            $(
+                /// Slave I2C device
+                pub $i2cx: OwnedI2cSlave<DEV, I2C>,
+            )*
+        }
+
+        #[cfg(feature = "alloc")]
+        impl<DEV, I2C> $owned<DEV, I2C> {
+            pub(crate) fn new(dev: DEV) -> Self {
+                let dev = Rc::new(dev);
+                $owned {
+                    $(
+                        $i2cx: OwnedI2cSlave(Rc::clone(&dev), $channel, PhantomData),
+                    )*
+                }
+            }
+        }
     }
 }
 parts!(
-    Parts; i2c0, 0x01, i2c1, 0x02, i2c2, 0x04, i2c3, 0x08, i2c4, 0x10, i2c5, 0x20, i2c6, 0x40, i2c7, 0x80
+    Parts, OwnedParts; i2c0, 0x01, i2c1, 0x02, i2c2, 0x04, i2c3, 0x08, i2c4, 0x10, i2c5, 0x20, i2c6, 0x40, i2c7, 0x80
 );
 parts!(
-    Parts2; i2c0, 0x01, i2c1, 0x02
+    Parts2, OwnedParts2; i2c0, 0x01, i2c1, 0x02
 );
 parts!(
-    Parts4; i2c0, 0x01, i2c1, 0x02, i2c2, 0x04, i2c3, 0x08
+    Parts4, OwnedParts4; i2c0, 0x01, i2c1, 0x02, i2c2, 0x04, i2c3, 0x08
 );
 
-impl<'a, DEV, I2C, E> i2c::Write for I2cSlave<'a, DEV, I2C>
-where
-    DEV: DoOnAcquired<I2C>,
-    I2C: i2c::Write<Error = E>,
-{
-    type Error = Error<E>;
-
-    fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
-        self.0.do_on_acquired(|mut dev| {
-            if dev.selected_channel_mask != self.1 {
-                dev.select_channels(self.1)?;
+// The slave I2C devices select their channel before each transfer.
+macro_rules! impl_slave {
+    ( [$($lt:lifetime)?] $slave:ty ) => {
+        impl<$($lt,)? DEV, I2C, E, A> i2c::Write<A> for $slave
+        where
+            DEV: DoOnAcquired<I2C>,
+            I2C: i2c::Write<Error = E> + i2c::Write<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
+        {
+            type Error = Error<E>;
+
+            fn write(&mut self, address: A, bytes: &[u8]) -> Result<(), Self::Error> {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels(self.1)?;
+                    dev.i2c.write(address, bytes).map_err(Error::I2C)
+                })
             }
-            dev.i2c.write(address, bytes).map_err(Error::I2C)
-        })
-    }
-}
+        }
+
+        impl<$($lt,)? DEV, I2C, E, A> i2c::Read<A> for $slave
+        where
+            DEV: DoOnAcquired<I2C>,
+            I2C: i2c::Write<Error = E> + i2c::Read<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
+        {
+            type Error = Error<E>;
 
-impl<'a, DEV, I2C, E> i2c::Read for I2cSlave<'a, DEV, I2C>
-where
-    DEV: DoOnAcquired<I2C>,
-    I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
-{
-    type Error = Error<E>;
-
-    fn read(&mut self, address: u8, buffer: &mut [u8]) -> Result<(), Self::Error> {
-        self.0.do_on_acquired(|mut dev| {
-            if dev.selected_channel_mask != self.1 {
-                dev.select_channels(self.1)?;
+            fn read(&mut self, address: A, buffer: &mut [u8]) -> Result<(), Self::Error> {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels(self.1)?;
+                    dev.i2c.read(address, buffer).map_err(Error::I2C)
+                })
             }
-            dev.i2c.read(address, buffer).map_err(Error::I2C)
-        })
-    }
-}
+        }
 
-impl<'a, DEV, I2C, E> i2c::WriteRead for I2cSlave<'a, DEV, I2C>
-where
-    DEV: DoOnAcquired<I2C>,
-    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
-{
-    type Error = Error<E>;
-
-    fn write_read(
-        &mut self,
-        address: u8,
-        bytes: &[u8],
-        buffer: &mut [u8],
-    ) -> Result<(), Self::Error> {
-        self.0.do_on_acquired(|mut dev| {
-            if dev.selected_channel_mask != self.1 {
-                dev.select_channels(self.1)?;
+        impl<$($lt,)? DEV, I2C, E, A> i2c::WriteRead<A> for $slave
+        where
+            DEV: DoOnAcquired<I2C>,
+            I2C: i2c::Write<Error = E> + i2c::WriteRead<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
+        {
+            type Error = Error<E>;
+
+            fn write_read(
+                &mut self,
+                address: A,
+                bytes: &[u8],
+                buffer: &mut [u8],
+            ) -> Result<(), Self::Error> {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels(self.1)?;
+                    dev.i2c
+                        .write_read(address, bytes, buffer)
+                        .map_err(Error::I2C)
+                })
             }
-            dev.i2c
-                .write_read(address, bytes, buffer)
-                .map_err(Error::I2C)
-        })
-    }
+        }
+
+        impl<$($lt,)? DEV, I2C, E, A> i2c::WriteIter<A> for $slave
+        where
+            DEV: DoOnAcquired<I2C>,
+            I2C: i2c::Write<Error = E> + i2c::WriteIter<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
+        {
+            type Error = Error<E>;
+
+            fn write<B>(&mut self, address: A, bytes: B) -> Result<(), Self::Error>
+            where
+                B: IntoIterator<Item = u8>,
+            {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels(self.1)?;
+                    i2c::WriteIter::write(&mut dev.i2c, address, bytes).map_err(Error::I2C)
+                })
+            }
+        }
+
+        impl<$($lt,)? DEV, I2C, E, A> i2c::WriteIterRead<A> for $slave
+        where
+            DEV: DoOnAcquired<I2C>,
+            I2C: i2c::Write<Error = E> + i2c::WriteIterRead<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
+        {
+            type Error = Error<E>;
+
+            fn write_iter_read<B>(
+                &mut self,
+                address: A,
+                bytes: B,
+                buffer: &mut [u8],
+            ) -> Result<(), Self::Error>
+            where
+                B: IntoIterator<Item = u8>,
+            {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels(self.1)?;
+                    dev.i2c
+                        .write_iter_read(address, bytes, buffer)
+                        .map_err(Error::I2C)
+                })
+            }
+        }
+
+        impl<$($lt,)? DEV, I2C, E, A> i2c::Transactional<A> for $slave
+        where
+            DEV: DoOnAcquired<I2C>,
+            I2C: i2c::Write<Error = E> + i2c::Transactional<A, Error = E>,
+            A: i2c::AddressMode + SlaveAddress,
+        {
+            type Error = Error<E>;
+
+            fn exec<'b>(
+                &mut self,
+                address: A,
+                operations: &mut [i2c::Operation<'b>],
+            ) -> Result<(), Self::Error> {
+                self.0.do_on_acquired(|dev| {
+                    dev.check_channel_address(self.1, address)?;
+                    dev.select_channels(self.1)?;
+                    dev.i2c.exec(address, operations).map_err(Error::I2C)
+                })
+            }
+        }
+    };
 }
+
+impl_slave!(['a] I2cSlave<'a, DEV, I2C>);
+#[cfg(feature = "alloc")]
+impl_slave!([] OwnedI2cSlave<DEV, I2C>);
@@ -0,0 +1,152 @@
+//! Control register encodings of the devices.
+//!
+//! The switches enable each channel through its own bit, while the
+//! multiplexers store the number of the single enabled channel next to
+//! an enable bit. Each device macro is given one of these encodings, so
+//! the rest of the driver can work with channel masks only.
+//!
+//! The switch encoding writes the channel mask unchanged, so the switch
+//! methods write masks directly and only decode the values they read.
+
+/// Encoding of the channel selection in the control register
+pub(crate) trait ControlRegister {
+    /// Mask of the channels of the device.
+    const CHANNELS: u8;
+
+    /// Encode a channel mask into the control register value.
+    ///
+    /// The mask must contain only channels of the device and, for
+    /// multiplexers, at most one channel.
+    fn encode(channels: u8) -> u8;
+
+    /// Decode the channel mask from a control register value.
+    ///
+    /// Any other bits, for example interrupt flags, are ignored.
+    fn decode(register: u8) -> u8;
+}
+
+macro_rules! switch_register {
+    ( $name:ident, $doc:expr, $channels:expr ) => {
+        #[doc = $doc]
+        pub(crate) struct $name;
+
+        impl ControlRegister for $name {
+            const CHANNELS: u8 = $channels;
+
+            fn encode(channels: u8) -> u8 {
+                channels
+            }
+
+            fn decode(register: u8) -> u8 {
+                register & Self::CHANNELS
+            }
+        }
+    };
+}
+
+switch_register!(Switch8, "Eight-channel switch encoding", 0xff);
+switch_register!(Switch4, "Four-channel switch encoding", 0x0f);
+switch_register!(Switch2, "Two-channel switch encoding", 0x03);
+
+macro_rules! mux_register {
+    ( $name:ident, $doc:expr, $channels:expr, $enable:expr, $index:expr ) => {
+        #[doc = $doc]
+        pub(crate) struct $name;
+
+        impl ControlRegister for $name {
+            const CHANNELS: u8 = $channels;
+
+            fn encode(channels: u8) -> u8 {
+                if channels == 0 {
+                    0
+                } else {
+                    $enable | channels.trailing_zeros() as u8
+                }
+            }
+
+            fn decode(register: u8) -> u8 {
+                if register & $enable != 0 {
+                    1 << (register & $index)
+                } else {
+                    0
+                }
+            }
+        }
+    };
+}
+
+mux_register!(
+    Mux8,
+    "Eight-channel multiplexer encoding with bit 3 as enable bit",
+    0xff,
+    0b0000_1000,
+    0b0000_0111
+);
+mux_register!(
+    Mux4,
+    "Four-channel multiplexer encoding with bit 2 as enable bit",
+    0x0f,
+    0b0000_0100,
+    0b0000_0011
+);
+mux_register!(
+    Mux2,
+    "Two-channel multiplexer encoding with bit 2 as enable bit",
+    0x03,
+    0b0000_0100,
+    0b0000_0001
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn switch_encodes_channel_bits() {
+        assert_eq!(0b1010_0101, Switch8::encode(0b1010_0101));
+        assert_eq!(0b1010_0101, Switch8::decode(0b1010_0101));
+        assert_eq!(0b0000_0101, Switch4::encode(0b0000_0101));
+        assert_eq!(0b0000_0010, Switch2::encode(0b0000_0010));
+    }
+
+    #[test]
+    fn switch_decode_ignores_interrupt_bits() {
+        assert_eq!(0b0000_0011, Switch4::decode(0b1010_0011));
+        assert_eq!(0b0000_0001, Switch2::decode(0b0010_0001));
+    }
+
+    #[test]
+    fn can_encode_and_decode_mux8() {
+        for index in 0..8 {
+            let register = Mux8::encode(1 << index);
+            assert_eq!(0b1000 | index, register);
+            assert_eq!(1 << index, Mux8::decode(register));
+        }
+        assert_eq!(0, Mux8::encode(0));
+        assert_eq!(0, Mux8::decode(0b0000_0111));
+    }
+
+    #[test]
+    fn can_encode_and_decode_mux4() {
+        for index in 0..4 {
+            let register = Mux4::encode(1 << index);
+            assert_eq!(0b100 | index, register);
+            assert_eq!(1 << index, Mux4::decode(register));
+            assert_eq!(1 << index, Mux4::decode(register | 0xf0));
+        }
+        assert_eq!(0, Mux4::encode(0));
+        assert_eq!(0, Mux4::decode(0b0000_0011));
+    }
+
+    #[test]
+    fn can_encode_and_decode_mux2() {
+        for index in 0..2 {
+            let register = Mux2::encode(1 << index);
+            assert_eq!(0b100 | index, register);
+            assert_eq!(1 << index, Mux2::decode(register));
+            assert_eq!(1 << index, Mux2::decode(register | 0b1111_0010));
+        }
+        assert_eq!(0, Mux2::encode(0));
+        assert_eq!(0, Mux2::decode(0b0000_0001));
+    }
+}
@@ -0,0 +1,103 @@
+//! Channel selection interface shared by all the devices.
+
+use crate::register::{ControlRegister, Mux2, Mux4, Mux8};
+use crate::{
+    check_channels, DeviceMutex, DoOnAcquired, Error, Ltc4306, Pca9540b, Pca9542a, Pca9547,
+    SelectChannels, Xca9543a, Xca9544a, Xca9545a, Xca9546a, Xca9548a, Xca954xaData,
+};
+use hal::blocking::i2c;
+
+/// Channel selection of any of the devices
+///
+/// This allows drivers to be generic over the device they are connected
+/// through. The channel masks work as in the `select_channels()` and
+/// `get_channel_status()` methods of the switches: bit 0 corresponds to
+/// channel 0, bit 1 to channel 1 and so on.
+pub trait I2cSwitch {
+    /// Error of the I²C bus
+    type BusError;
+
+    /// Select which channels are enabled.
+    ///
+    /// Selecting channels that do not exist for the specific device
+    /// returns `Error::InvalidChannelMask`. The multiplexers can enable at
+    /// most one channel, so they return it as well for several channels.
+    /// Selecting no channels on a multiplexer always writes to the device,
+    /// like its `disable()`.
+    fn select_channels(&mut self, channels: u8) -> Result<(), Error<Self::BusError>>;
+
+    /// Get the mask of the enabled channels.
+    fn get_channel_status(&self) -> Result<u8, Error<Self::BusError>>;
+}
+
+macro_rules! impl_switch {
+    ( $name:ident ) => {
+        impl<I2C, M, E> I2cSwitch for $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+        {
+            type BusError = E;
+
+            fn select_channels(&mut self, channels: u8) -> Result<(), Error<E>> {
+                $name::select_channels(self, channels)
+            }
+
+            fn get_channel_status(&self) -> Result<u8, Error<E>> {
+                $name::get_channel_status(self)
+            }
+        }
+    };
+}
+
+impl_switch!(Xca9548a);
+impl_switch!(Xca9543a);
+impl_switch!(Xca9545a);
+impl_switch!(Xca9546a);
+
+macro_rules! impl_mux_switch {
+    ( $name:ident, $reg:ident ) => {
+        impl<I2C, M, E> I2cSwitch for $name<I2C, M>
+        where
+            M: DeviceMutex<Xca954xaData<I2C>>,
+            I2C: i2c::Write<Error = E> + i2c::Read<Error = E>,
+        {
+            type BusError = E;
+
+            fn select_channels(&mut self, channels: u8) -> Result<(), Error<E>> {
+                let channels = check_channels(channels, $reg::CHANNELS)?;
+                match channels.count_ones() {
+                    // Always written, like the inherent `disable()`
+                    0 => $name::disable(self),
+                    1 => self.do_on_acquired(|dev| dev.select_channels($reg::encode(channels))),
+                    _ => Err(Error::InvalidChannelMask),
+                }
+            }
+
+            fn get_channel_status(&self) -> Result<u8, Error<E>> {
+                Ok($reg::decode(self.read_control_register()?))
+            }
+        }
+    };
+}
+
+impl_mux_switch!(Xca9544a, Mux4);
+impl_mux_switch!(Pca9542a, Mux2);
+impl_mux_switch!(Pca9540b, Mux2);
+impl_mux_switch!(Pca9547, Mux8);
+
+impl<I2C, M, E> I2cSwitch for Ltc4306<I2C, M>
+where
+    M: DeviceMutex<Xca954xaData<I2C>>,
+    I2C: i2c::Write<Error = E> + i2c::WriteRead<Error = E>,
+{
+    type BusError = E;
+
+    fn select_channels(&mut self, channels: u8) -> Result<(), Error<E>> {
+        Ltc4306::select_channels(self, channels)
+    }
+
+    fn get_channel_status(&self) -> Result<u8, Error<E>> {
+        Ltc4306::get_channel_status(self)
+    }
+}
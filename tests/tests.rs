@@ -1,183 +1,3211 @@
 extern crate embedded_hal;
 extern crate embedded_hal_mock as hal;
-use hal::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+use hal::eh0::delay::NoopDelay;
+use hal::eh0::digital::{Mock as PinMock, State as PinState, Transaction as PinTrans};
+use hal::eh0::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+use hal::eh0::MockError;
+use std::io::ErrorKind;
 extern crate xca9548a;
-use xca9548a::{SlaveAddr, Xca9548a, Xca9543a, Xca9545a};
+use xca9548a::{
+    Channel, ChannelConfig, Channels, Config, Error, Ltc4306, Pca9540b, Pca9542a, Pca9547,
+    SlaveAddr, Xca9543a, Xca9544a, Xca9545a, Xca9546a, Xca9548a,
+};
 
 const DEV_ADDR: u8 = 0b111_0000;
 
 use embedded_hal::prelude::*;
+const BUS_ERROR: MockError = MockError::Io(ErrorKind::Other);
 const SLAVE_ADDR: u8 = 0b010_0000;
 const SLAVE_WRITE_DATA: [u8; 2] = [0b0101_0101, 0b1010_1010];
 const SLAVE_READ_DATA: [u8; 2] = [0b1001_1001, 0b0110_0110];
 
+/// Delay recording the requested delays.
+#[derive(Default)]
+struct RecordingDelay(Vec<u32>);
+
+impl embedded_hal::blocking::delay::DelayUs<u32> for RecordingDelay {
+    fn delay_us(&mut self, us: u32) {
+        self.0.push(us);
+    }
+}
+
+/// Minimal slave driver taking ownership of an I2C bus.
+struct Driver<I2C> {
+    i2c: I2C,
+}
+
+impl<I2C, E> Driver<I2C>
+where
+    I2C: embedded_hal::blocking::i2c::WriteRead<Error = E>,
+{
+    fn new(i2c: I2C) -> Self {
+        Driver { i2c }
+    }
+
+    fn read_register(&mut self, register: u8) -> Result<u8, E> {
+        let mut data = [0];
+        self.i2c
+            .write_read(SLAVE_ADDR, &[register], &mut data)
+            .and(Ok(data[0]))
+    }
+}
+
 macro_rules! test_interrupt {
     ( $name:ident, $channels:expr ) => {
-
         #[test]
         fn can_get_interrupt_status() {
-            let transactions = [I2cTrans::read(DEV_ADDR, vec![0b1010_0000 & ($channels << 4)])];
-            let mut switch = new(&transactions);
+            let transactions = [I2cTrans::read(
+                DEV_ADDR,
+                vec![0b1010_0000 & ($channels << 4)],
+            )];
+            let switch = new(&transactions);
             let read_status = switch.get_interrupt_status().unwrap();
             assert_eq!(0b0000_1010 & $channels, read_status);
             switch.destroy().done();
         }
-    }
+
+        #[cfg(feature = "unproven")]
+        #[test]
+        fn can_wait_for_interrupt() {
+            let transactions = [I2cTrans::read(DEV_ADDR, vec![0b0010_0000])];
+            let pin_transactions = [
+                PinTrans::get(PinState::High),
+                PinTrans::get(PinState::High),
+                PinTrans::get(PinState::Low),
+            ];
+            let mut int_pin = PinMock::new(&pin_transactions);
+            let mut delay = RecordingDelay::default();
+            let mut switch = new(&transactions);
+            let status = switch
+                .wait_for_interrupt(&mut int_pin, &mut delay, 100)
+                .unwrap();
+            assert_eq!(Some(0b0000_0010), status);
+            assert_eq!(vec![10, 10], delay.0);
+            int_pin.done();
+            switch.destroy().done();
+        }
+
+        #[cfg(feature = "unproven")]
+        #[test]
+        fn wait_for_interrupt_times_out() {
+            let pin_transactions = [
+                PinTrans::get(PinState::High),
+                PinTrans::get(PinState::High),
+                PinTrans::get(PinState::High),
+            ];
+            let mut int_pin = PinMock::new(&pin_transactions);
+            let mut delay = RecordingDelay::default();
+            let mut switch = new(&[]);
+            let status = switch
+                .wait_for_interrupt(&mut int_pin, &mut delay, 20)
+                .unwrap();
+            assert_eq!(None, status);
+            assert_eq!(vec![10, 10], delay.0);
+            int_pin.done();
+            switch.destroy().done();
+        }
+    };
 }
 
 macro_rules! test_ch_out_of_range {
     ( $name:ident, $channel:expr ) => {
+        #[test]
+        fn reject_ch_out_of_range() {
+            let mut switch = new(&[]);
+            match switch.select_channels(!$channel) {
+                Err(Error::InvalidChannelMask) => {}
+                _ => panic!("Invalid channel mask not reported"),
+            }
+            switch.destroy().done();
+        }
 
         #[test]
-        fn ignore_ch_out_of_range() {
-            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
-            let mut switch = new(&transactions);
-            switch.select_channels(0b1000_0001).unwrap();
+        fn reject_single_ch_out_of_range() {
+            let mut switch = new(&[]);
+            match switch.select_channel(Channel::Ch7) {
+                Err(Error::InvalidChannelMask) => {}
+                _ => panic!("Invalid channel not reported"),
+            }
             switch.destroy().done();
         }
-    }
+
+        #[test]
+        fn reject_channel_index_out_of_range() {
+            let mut switch = new(&[]);
+            for index in [($channel as u8).count_ones() as usize, 8, 256].iter() {
+                match switch.select_channel_index(*index) {
+                    Err(Error::InvalidChannelMask) => {}
+                    _ => panic!("Invalid channel index {} not reported", index),
+                }
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn reject_each_ch_out_of_range() {
+            let mut switch = new(&[]);
+            for channel in Channel::iter_from_mask(!$channel) {
+                match switch.select_channel(channel) {
+                    Err(Error::InvalidChannelMask) => {}
+                    _ => panic!("Invalid channel {:?} not reported", channel),
+                }
+            }
+            switch.destroy().done();
+        }
+    };
 }
 
 macro_rules! test_device {
     ( $name:ident, $channels:expr ) => {
-
         fn new(transactions: &[I2cTrans]) -> $name<I2cMock> {
-            $name::new(I2cMock::new(&transactions), SlaveAddr::default())
+            $name::new(I2cMock::new(transactions), SlaveAddr::default())
         }
 
+        #[test]
+        fn can_select_channels_with_alternative_address() {
+            let transactions = [I2cTrans::write(0b111_0101, vec![0x01])];
+            let mut switch = $name::new(
+                I2cMock::new(&transactions),
+                SlaveAddr::Alternative(true, false, true),
+            );
+            switch.select_channels(0x01).unwrap();
+            switch.destroy().done();
+        }
 
         #[test]
-        fn can_select_channels() {
-            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
-            let mut switch = new(&transactions);
+        fn can_select_channels_with_raw_address() {
+            let transactions = [I2cTrans::write(0x25, vec![0x01])];
+            let mut switch = $name::new(I2cMock::new(&transactions), SlaveAddr::Raw(0x25));
             switch.select_channels(0x01).unwrap();
             switch.destroy().done();
         }
 
         #[test]
-        fn can_get_channel_status() {
-            let transactions = [I2cTrans::read(DEV_ADDR, vec![0b0101_0101 & $channels])];
+        fn default_uses_default_address() {
+            #[derive(Default)]
+            struct DefaultI2c;
+
+            let switch: $name<DefaultI2c> = Default::default();
+            assert_eq!(DEV_ADDR, switch.address());
+        }
+
+        #[test]
+        fn can_get_address() {
+            let switch = $name::new(I2cMock::new(&[]), SlaveAddr::Alternative(true, false, true));
+            assert_eq!(0b111_0101, switch.address());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_get_8_bit_addresses() {
+            let switch = $name::new(I2cMock::new(&[]), SlaveAddr::Alternative(true, false, true));
+            assert_eq!(0b1110_1010, switch.write_address());
+            assert_eq!(0b1110_1011, switch.read_address());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_set_address() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(0b111_0101, vec![0x01]),
+            ];
             let mut switch = new(&transactions);
-            let read_status = switch.get_channel_status().unwrap();
-            assert_eq!(0b0101_0101 & $channels, read_status);
+            switch.select_channels(0x01).unwrap();
+            switch.set_address(SlaveAddr::Alternative(true, false, true));
+            assert_eq!(0b111_0101, switch.address());
+            switch.select_channels(0x01).unwrap();
             switch.destroy().done();
         }
 
         #[test]
-        fn can_write_to_slave() {
+        fn can_reset() {
+            let pin_transactions = [PinTrans::set(PinState::Low), PinTrans::set(PinState::High)];
+            let mut reset_pin = PinMock::new(&pin_transactions);
+            let mut switch = new(&[]);
+            switch.reset(&mut reset_pin, &mut NoopDelay::new()).unwrap();
+            switch.destroy().done();
+            reset_pin.done();
+        }
+
+        #[test]
+        fn reset_takes_same_delay_as_other_methods() {
+            let pin_transactions = [PinTrans::set(PinState::Low), PinTrans::set(PinState::High)];
+            let mut reset_pin = PinMock::new(&pin_transactions);
+            let mut delay = RecordingDelay(Vec::new());
+            let mut switch = new(&[]);
+            switch.reset(&mut reset_pin, &mut delay).unwrap();
+            assert_eq!(vec![1], delay.0);
+            switch.destroy().done();
+            reset_pin.done();
+        }
+
+        #[test]
+        fn reset_discards_cached_selection() {
             let transactions = [
                 I2cTrans::write(DEV_ADDR, vec![0x01]),
-                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
             ];
+            let pin_transactions = [PinTrans::set(PinState::Low), PinTrans::set(PinState::High)];
+            let mut reset_pin = PinMock::new(&pin_transactions);
             let mut switch = new(&transactions);
-            switch.select_channels(0b0000_0001).unwrap();
-            switch.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+            switch.select_channels(0x01).unwrap();
+            switch.reset(&mut reset_pin, &mut NoopDelay::new()).unwrap();
+            switch.select_channels(0x01).unwrap();
             switch.destroy().done();
+            reset_pin.done();
         }
 
         #[test]
-        fn can_read_from_slave() {
+        fn recover_discards_cached_selection() {
             let transactions = [
                 I2cTrans::write(DEV_ADDR, vec![0x01]),
-                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
             ];
             let mut switch = new(&transactions);
-            switch.select_channels(0b0000_0001).unwrap();
+            switch.select_channels(0x01).unwrap();
+            switch.recover();
+            switch.select_channels(0x01).unwrap();
+            switch.destroy().done();
+        }
 
-            let mut read_data = [0; 2];
-            switch.read(SLAVE_ADDR, &mut read_data).unwrap();
-            assert_eq!(read_data, SLAVE_READ_DATA);
+        #[test]
+        fn can_select_channels() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
             switch.destroy().done();
         }
+
         #[test]
-        fn can_write_read_from_slave() {
+        fn rejects_own_address() {
+            let mut switch = new(&[]);
+            match switch.write(DEV_ADDR, &[0xff]) {
+                Err(Error::AddressConflict) => {}
+                _ => panic!("Address conflict not reported"),
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn slave_rejects_own_address() {
+            let switch = new(&[]);
+            {
+                let mut parts = switch.split();
+                let mut read_data = [0; 1];
+                match parts.i2c0.read(DEV_ADDR, &mut read_data) {
+                    Err(Error::AddressConflict) => {}
+                    _ => panic!("Address conflict not reported"),
+                }
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_allow_own_address() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0xff & $channels])];
+            let config = Config {
+                allow_own_address: true,
+                ..Config::default()
+            };
+            let mut switch =
+                $name::new_with_config(I2cMock::new(&transactions), SlaveAddr::default(), config);
+            switch.write(DEV_ADDR, &[0xff & $channels]).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn detects_channel_drift_after_write() {
             let transactions = [
                 I2cTrans::write(DEV_ADDR, vec![0x01]),
-                I2cTrans::write_read(
-                    SLAVE_ADDR,
-                    SLAVE_WRITE_DATA.to_vec(),
-                    SLAVE_READ_DATA.to_vec(),
-                ),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::read(DEV_ADDR, vec![0x00]),
+            ];
+            let config = Config {
+                verify_channel_after_write: true,
+                ..Config::default()
+            };
+            let mut switch =
+                $name::new_with_config(I2cMock::new(&transactions), SlaveAddr::default(), config);
+            switch
+                .write_to_channel(0x01, SLAVE_ADDR, &SLAVE_WRITE_DATA)
+                .unwrap();
+            match switch.write_to_channel(0x01, SLAVE_ADDR, &SLAVE_WRITE_DATA) {
+                Err(Error::VerificationFailed {
+                    expected: 0x01,
+                    actual: 0x00,
+                }) => {}
+                _ => panic!("Channel drift not reported"),
+            }
+            assert_eq!(None, switch.cached_channel_status());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_reset_bus_via_mux() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(DEV_ADDR, vec![0x00]),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
             ];
             let mut switch = new(&transactions);
-            switch.select_channels(0b0000_0001).unwrap();
+            let mut delay = RecordingDelay::default();
+            switch.reset_bus_via_mux(&mut delay).unwrap();
+            assert_eq!(vec![35_000], delay.0);
+            assert_eq!(Some(0x02), switch.cached_channel_status());
+            switch.destroy().done();
+        }
 
-            let mut read_data = [0; 2];
+        #[test]
+        fn can_scan_channel() {
+            let mut transactions = vec![I2cTrans::write(DEV_ADDR, vec![0x01])];
+            for address in (0..0x80).filter(|a| *a != DEV_ADDR) {
+                let read = I2cTrans::read(address, vec![]);
+                if address == 0x48 {
+                    transactions.push(read);
+                } else {
+                    transactions.push(read.with_error(BUS_ERROR));
+                }
+            }
+            let mut switch = new(&transactions);
+            let found = switch.scan_channel(Channel::Ch0).unwrap();
+            for (address, found) in found.iter().enumerate() {
+                assert_eq!(*found, address == 0x48);
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_select_channels_with_delay() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+            let mut switch = new(&transactions);
+            let mut delay = RecordingDelay::default();
             switch
-                .write_read(SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data)
+                .select_channels_with_delay(0x01, &mut delay, 50)
                 .unwrap();
-            assert_eq!(read_data, SLAVE_READ_DATA);
+            assert_eq!(delay.0, [50]);
             switch.destroy().done();
         }
 
         #[test]
-        fn can_split_and_communicate_with_slave() {
-            let slave_read_data_2 = [0xAB, 0xCD];
+        fn can_select_channel_set() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0b0000_0011])];
+            let mut switch = new(&transactions);
+            switch
+                .select_channel_set(&[Channel::Ch1, Channel::Ch0, Channel::Ch1])
+                .unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_disable_all_channels() {
             let transactions = [
                 I2cTrans::write(DEV_ADDR, vec![0x01]),
-                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
-                I2cTrans::write(DEV_ADDR, vec![0x02]),
-                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+                I2cTrans::write(DEV_ADDR, vec![0x00]),
+                I2cTrans::write(DEV_ADDR, vec![0x00]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
+            switch.disable_all_channels().unwrap();
+            switch.disable_all_channels().unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_select_all_channels() {
+            assert_eq!($channels, $name::<I2cMock>::ALL_CHANNELS);
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![$channels]),
+                I2cTrans::write(DEV_ADDR, vec![$channels]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_all_channels().unwrap();
+            switch.select_all_channels().unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_select_channels_verified() {
+            let transactions = [
                 I2cTrans::write(DEV_ADDR, vec![0x01]),
-                I2cTrans::write_read(
-                    SLAVE_ADDR,
-                    SLAVE_WRITE_DATA.to_vec(),
-                    slave_read_data_2.to_vec(),
-                ),
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
             ];
-            let switch = new(&transactions);
-            {
-                let mut read_data_1 = [0; 2];
-                let mut read_data_2 = [0; 2];
-                let mut parts = switch.split();
-                parts.i2c0.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
-                parts.i2c1.read(SLAVE_ADDR, &mut read_data_1).unwrap();
-                parts
-                    .i2c0
-                    .write_read(SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data_2)
-                    .unwrap();
-                assert_eq!(read_data_1, SLAVE_READ_DATA);
-                assert_eq!(read_data_2, slave_read_data_2);
-            }
+            let mut switch = new(&transactions);
+            switch.select_channels_verified(0x01).unwrap();
             switch.destroy().done();
         }
 
         #[test]
-        fn when_split_only_change_channel_if_necessary() {
+        fn select_channels_verified_reports_mismatch() {
             let transactions = [
                 I2cTrans::write(DEV_ADDR, vec![0x01]),
-                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
-                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+                I2cTrans::read(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
             ];
-            let switch = new(&transactions);
-            {
-                let mut read_data = [0; 2];
-                let mut parts = switch.split();
-                parts.i2c0.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
-                parts.i2c0.read(SLAVE_ADDR, &mut read_data).unwrap();
-                assert_eq!(read_data, SLAVE_READ_DATA);
+            let mut switch = new(&transactions);
+            match switch.select_channels_verified(0x01) {
+                Err(Error::VerificationFailed {
+                    expected: 0x01,
+                    actual: 0x02,
+                }) => {}
+                _ => panic!("Verification failure not reported"),
             }
+            // Selection is unknown so it is written again
+            switch.select_channels(0x01).unwrap();
             switch.destroy().done();
         }
-    }
-}
 
-mod test_xca9548a {
-    use super::*;
-    test_device!(Xca9548a, 0xff);
-}
+        #[test]
+        fn select_channels_verified_reports_partial_enable() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x03]),
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
+            ];
+            let mut switch = new(&transactions);
+            match switch.select_channels_verified(0x03) {
+                Err(Error::VerificationFailed { expected, actual }) => {
+                    assert_eq!(0x02, expected & !actual);
+                }
+                _ => panic!("Verification failure not reported"),
+            }
+            switch.destroy().done();
+        }
 
-mod test_xca9545a {
-    use super::*;
-    test_device!(Xca9545a, 0x0f);
-    test_interrupt!(Xca9545a, 0x0f);
-    test_ch_out_of_range!(Xca9545a, 0x0f);
-}
+        #[test]
+        fn can_run_self_test() {
+            let mut transactions = vec![];
+            for pattern in [0x00, 0xff, 0xaa, 0x55].iter() {
+                let pattern = pattern & $channels;
+                transactions.push(I2cTrans::write(DEV_ADDR, vec![pattern]));
+                transactions.push(I2cTrans::read(DEV_ADDR, vec![pattern]));
+            }
+            transactions.push(I2cTrans::write(DEV_ADDR, vec![0x00]));
+            let mut switch = new(&transactions);
+            switch.self_test().unwrap();
+            assert_eq!(Some(0x00), switch.cached_channel_status());
+            switch.destroy().done();
+        }
 
-mod test_xca9543a {
-    use super::*;
-    test_device!(Xca9543a, 0x03);
-    test_interrupt!(Xca9543a, 0x03);
-    test_ch_out_of_range!(Xca9543a, 0x03);
-}
+        #[test]
+        fn self_test_reports_mismatch() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x00]),
+                I2cTrans::read(DEV_ADDR, vec![0x00]),
+                I2cTrans::write(DEV_ADDR, vec![$channels]),
+                I2cTrans::read(DEV_ADDR, vec![0x00]),
+            ];
+            let mut switch = new(&transactions);
+            match switch.self_test() {
+                Err(Error::VerificationFailed {
+                    expected: $channels,
+                    actual: 0x00,
+                }) => {}
+                _ => panic!("Verification failure not reported"),
+            }
+            assert_eq!(None, switch.cached_channel_status());
+            switch.destroy().done();
+        }
 
+        #[test]
+        fn can_create_with_channels() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+            let mut switch =
+                $name::new_with_channels(I2cMock::new(&transactions), SlaveAddr::default(), 0x01)
+                    .unwrap();
+            // Selection is already known
+            switch.select_channels(0x01).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_create_with_no_channels() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x00])];
+            let switch =
+                $name::new_with_channels(I2cMock::new(&transactions), SlaveAddr::default(), 0)
+                    .unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_select_channel_by_index() {
+            let last = ($channels as u8).count_ones() as usize - 1;
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0001]),
+                I2cTrans::write(DEV_ADDR, vec![1 << last]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channel_index(0).unwrap();
+            switch.select_channel_index(last).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_select_channels_with_single_channel() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0b0000_0010])];
+            let mut switch = new(&transactions);
+            switch.select_channels(Channel::Ch1).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_select_channels_with_channel_set() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0011]),
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0001]),
+            ];
+            let mut switch = new(&transactions);
+            switch
+                .select_channels(Channels::CH0 | Channels::CH1)
+                .unwrap();
+            switch.select_channels(0b0000_0001).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn select_channels_retry_retries_failed_writes() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]).with_error(BUS_ERROR),
+                I2cTrans::write(DEV_ADDR, vec![0x01]).with_error(BUS_ERROR),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels_retry(0x01, 2).unwrap();
+            assert_eq!(Some(0x01), switch.cached_channel_status());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn select_channels_retry_returns_last_error() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]).with_error(BUS_ERROR),
+                I2cTrans::write(DEV_ADDR, vec![0x01]).with_error(BUS_ERROR),
+            ];
+            let mut switch = new(&transactions);
+            match switch.select_channels_retry(0x01, 1) {
+                Err(Error::I2C(_)) => {}
+                _ => panic!("Write error not reported"),
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_write_to_channel() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            switch
+                .write_to_channel(0x01, SLAVE_ADDR, &SLAVE_WRITE_DATA)
+                .unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_read_from_channel() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            let mut read_data = [0; 2];
+            switch
+                .read_from_channel(0x01, SLAVE_ADDR, &mut read_data)
+                .unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_write_read_on_channel() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::write_read(
+                    SLAVE_ADDR,
+                    SLAVE_WRITE_DATA.to_vec(),
+                    SLAVE_READ_DATA.to_vec(),
+                ),
+            ];
+            let mut switch = new(&transactions);
+            let mut read_data = [0; 2];
+            switch
+                .write_read_on_channel(0x02, SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data)
+                .unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_read_raw_control_register() {
+            let transactions = [I2cTrans::read(DEV_ADDR, vec![0b1010_0101])];
+            let switch = new(&transactions);
+            assert_eq!(0b1010_0101, switch.read_control_register().unwrap());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn rejects_address_not_allowed_on_channel() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            let config = ChannelConfig {
+                allowed_addresses: [Some(SLAVE_ADDR), None, None, None],
+                ..ChannelConfig::default()
+            };
+            switch.set_channel_config(Channel::Ch0, config).unwrap();
+            match switch.write_to_channel(0x01, SLAVE_ADDR + 1, &SLAVE_WRITE_DATA) {
+                Err(Error::AddressConflict) => {}
+                _ => panic!("Address not allowed on channel not reported"),
+            }
+            {
+                let mut parts = switch.split();
+                match parts.i2c0.write(SLAVE_ADDR + 1, &SLAVE_WRITE_DATA) {
+                    Err(Error::AddressConflict) => {}
+                    _ => panic!("Address not allowed on channel not reported"),
+                }
+            }
+            let mut read_data = [0; 2];
+            switch
+                .read_from(Channel::Ch0, SLAVE_ADDR, &mut read_data)
+                .unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_poll_status_through_shared_reference() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
+            ];
+            let switch = new(&transactions);
+            {
+                let _parts = switch.split();
+                let shared = &switch;
+                assert_eq!(0x01, shared.get_channel_status().unwrap());
+                assert!(shared.is_connected().unwrap());
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn is_connected_when_device_acknowledges() {
+            let transactions = [I2cTrans::read(DEV_ADDR, vec![0])];
+            let switch = new(&transactions);
+            assert!(switch.is_connected().unwrap());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn is_not_connected_when_device_does_not_acknowledge() {
+            let transactions = [I2cTrans::read(DEV_ADDR, vec![0]).with_error(BUS_ERROR)];
+            let switch = new(&transactions);
+            assert!(!switch.is_connected().unwrap());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_check_if_channel_is_enabled() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0010]),
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0010]),
+            ];
+            let switch = new(&transactions);
+            assert!(switch.is_channel_enabled(Channel::Ch1).unwrap());
+            assert!(!switch.is_channel_enabled(Channel::Ch0).unwrap());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_check_if_channel_is_enabled_in_cache() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0b0000_0010])];
+            let mut switch = new(&transactions);
+            switch.select_channels(0b0000_0010).unwrap();
+            assert_eq!(Some(true), switch.is_channel_enabled_cached(Channel::Ch1));
+            assert_eq!(Some(false), switch.is_channel_enabled_cached(Channel::Ch0));
+            switch.recover();
+            assert_eq!(None, switch.is_channel_enabled_cached(Channel::Ch1));
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn assumes_por_state_on_creation() {
+            let mut switch = new(&[]);
+            assert_eq!(
+                Some($name::<I2cMock>::POR_CONTROL_REGISTER),
+                switch.cached_channel_status()
+            );
+            switch.select_channels(0).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn cache_is_populated_after_select_channels() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
+            assert_eq!(Some(0x01), switch.cached_channel_status());
+            switch.recover();
+            assert_eq!(None, switch.cached_channel_status());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn disables_channels_on_drop() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x00]),
+            ];
+            let mut i2c = I2cMock::new(&transactions);
+            let mut switch = $name::new(i2c.clone(), SlaveAddr::default()).disable_on_drop();
+            switch.select_channels(0x01).unwrap();
+            drop(switch);
+            i2c.done();
+        }
+
+        #[test]
+        fn into_inner_does_not_disable_channels() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+            let mut switch = new(&transactions).disable_on_drop();
+            switch.select_channels(0x01).unwrap();
+            switch.into_inner().destroy().done();
+        }
+
+        #[test]
+        fn can_use_raw_bus_on_channel() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            ];
+            let switch = new(&transactions);
+            let read_data = switch
+                .with_raw(0x02, |i2c| {
+                    let mut data = [0; 2];
+                    i2c.read(SLAVE_ADDR, &mut data).map(|_| data)
+                })
+                .unwrap()
+                .unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn disables_channels_before_upstream_transfers() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x00]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
+            switch
+                .write_upstream(SLAVE_ADDR, &SLAVE_WRITE_DATA)
+                .unwrap();
+            let mut read_data = [0; 2];
+            switch.read_upstream(SLAVE_ADDR, &mut read_data).unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_send_quick_commands() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::read(SLAVE_ADDR, vec![]),
+                I2cTrans::write(SLAVE_ADDR, vec![]),
+            ];
+            let mut switch = new(&transactions);
+            switch.quick_command(0x01, SLAVE_ADDR, true).unwrap();
+            switch.quick_command(0x01, SLAVE_ADDR, false).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_read_many_under_one_selection() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+                I2cTrans::read(SLAVE_ADDR + 1, vec![0x12]),
+            ];
+            let mut switch = new(&transactions);
+            let mut first = [0; 2];
+            let mut second = [0; 1];
+            switch
+                .read_many(
+                    0x02,
+                    &mut [(SLAVE_ADDR, &mut first), (SLAVE_ADDR + 1, &mut second)],
+                )
+                .unwrap();
+            assert_eq!(first, SLAVE_READ_DATA);
+            assert_eq!(second, [0x12]);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn read_many_without_reads_only_selects() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x02])];
+            let mut switch = new(&transactions);
+            switch.read_many(0x02, &mut []).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_run_several_transfers_on_channels() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+                I2cTrans::read(SLAVE_ADDR + 1, SLAVE_READ_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            let (first, second) = switch
+                .run_on_channels(0x02, |i2c| {
+                    let mut first = [0; 2];
+                    let mut second = [0; 2];
+                    i2c.read(SLAVE_ADDR, &mut first)?;
+                    i2c.read(SLAVE_ADDR + 1, &mut second)?;
+                    Ok((first, second))
+                })
+                .unwrap();
+            assert_eq!(first, SLAVE_READ_DATA);
+            assert_eq!(second, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn run_on_channels_returns_bus_error() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()).with_error(BUS_ERROR),
+            ];
+            let mut switch = new(&transactions);
+            let result = switch.run_on_channels(0x02, |i2c| {
+                let mut data = [0; 2];
+                i2c.read(SLAVE_ADDR, &mut data)
+            });
+            match result {
+                Err(Error::I2C(_)) => {}
+                _ => panic!("Read error not reported"),
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn is_not_acquirable_during_transfer() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+            let switch = new(&transactions);
+            assert!(switch.is_acquirable());
+            let acquirable = switch.with_raw(0x01, |_| switch.is_acquirable()).unwrap();
+            assert!(!acquirable);
+            assert!(switch.is_acquirable());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn rejects_reentrant_access_during_transfer() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+            let switch = new(&transactions);
+            {
+                let mut parts = switch.split();
+                let result = switch
+                    .with_raw(0x01, |_| parts.i2c1.write(SLAVE_ADDR, &SLAVE_WRITE_DATA))
+                    .unwrap();
+                match result {
+                    Err(Error::CouldNotAcquireDevice) => {}
+                    _ => panic!("Re-entrant access not reported"),
+                }
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn slow_channel_delays_transfers() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+                I2cTrans::write_read(
+                    SLAVE_ADDR,
+                    SLAVE_WRITE_DATA.to_vec(),
+                    SLAVE_READ_DATA.to_vec(),
+                ),
+            ];
+            let mut switch = new(&transactions);
+            let config = ChannelConfig {
+                pre_transfer_delay_us: 50,
+                ..ChannelConfig::default()
+            };
+            switch.set_channel_config(Channel::Ch1, config).unwrap();
+            let mut delay = RecordingDelay::default();
+            let mut read_data = [0; 2];
+            switch
+                .write_to_with_delay(Channel::Ch1, SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut delay)
+                .unwrap();
+            switch
+                .read_from_with_delay(Channel::Ch1, SLAVE_ADDR, &mut read_data, &mut delay)
+                .unwrap();
+            switch
+                .write_read_from_with_delay(
+                    Channel::Ch1,
+                    SLAVE_ADDR,
+                    &SLAVE_WRITE_DATA,
+                    &mut read_data,
+                    &mut delay,
+                )
+                .unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            assert_eq!(vec![50, 50, 50], delay.0);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn unconfigured_channel_does_not_delay_transfers() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            let config = ChannelConfig {
+                pre_transfer_delay_us: 50,
+                ..ChannelConfig::default()
+            };
+            switch.set_channel_config(Channel::Ch1, config).unwrap();
+            let mut delay = RecordingDelay::default();
+            switch
+                .write_to_with_delay(Channel::Ch0, SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut delay)
+                .unwrap();
+            assert!(delay.0.is_empty());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_write_to_single_channel() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            switch
+                .write_to(Channel::Ch0, SLAVE_ADDR, &SLAVE_WRITE_DATA)
+                .unwrap();
+            switch
+                .write_to(Channel::Ch1, SLAVE_ADDR, &SLAVE_WRITE_DATA)
+                .unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_read_from_single_channel() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            let mut read_data = [0; 2];
+            switch
+                .read_from(Channel::Ch1, SLAVE_ADDR, &mut read_data)
+                .unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_write_read_from_single_channel() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write_read(
+                    SLAVE_ADDR,
+                    SLAVE_WRITE_DATA.to_vec(),
+                    SLAVE_READ_DATA.to_vec(),
+                ),
+            ];
+            let mut switch = new(&transactions);
+            let mut read_data = [0; 2];
+            switch
+                .write_read_from(Channel::Ch0, SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data)
+                .unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn selecting_same_channels_again_does_nothing() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
+            switch.select_channels(0x01).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_force_select_channels() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
+            switch.force_select_channels(0x01).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn failed_selection_is_sent_again() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x02]).with_error(BUS_ERROR),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
+            switch.select_channels(0x02).unwrap_err();
+            switch.select_channels(0x02).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_select_single_channel() {
+            let transactions = [I2cTrans::write(DEV_ADDR, vec![0b0000_0010])];
+            let mut switch = new(&transactions);
+            switch.select_channel(Channel::Ch1).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_enable_channel() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0001]),
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0011]),
+            ];
+            let mut switch = new(&transactions);
+            switch.enable_channel(Channel::Ch1).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_select_channels_in_order() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0000]),
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0010]),
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0010]),
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0010]),
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0011]),
+            ];
+            let mut switch = new(&transactions);
+            switch
+                .select_channels_ordered(&[Channel::Ch1, Channel::Ch1, Channel::Ch0])
+                .unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn ensure_channels_enables_missing_channels() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0001]),
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0011]),
+            ];
+            let mut switch = new(&transactions);
+            assert!(switch.ensure_channels(0b0000_0011).unwrap());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn ensure_channels_does_not_write_enabled_channels() {
+            let transactions = [I2cTrans::read(DEV_ADDR, vec![0b0000_0011])];
+            let mut switch = new(&transactions);
+            assert!(!switch.ensure_channels(0b0000_0010).unwrap());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_disable_channel() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0011]),
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0010]),
+            ];
+            let mut switch = new(&transactions);
+            switch.disable_channel(Channel::Ch0).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn enable_and_disable_channel_write_even_if_cached() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0010]),
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0000]),
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0010]),
+                I2cTrans::read(DEV_ADDR, vec![0b0000_0011]),
+                I2cTrans::write(DEV_ADDR, vec![0b0000_0010]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0b0000_0010).unwrap();
+            switch.enable_channel(Channel::Ch1).unwrap();
+            switch.disable_channel(Channel::Ch0).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn with_channels_restores_previous_selection() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+            ];
+            let mut switch = new(&transactions);
+            switch
+                .with_channels(0x02, |switch| switch.write(SLAVE_ADDR, &SLAVE_WRITE_DATA))
+                .unwrap()
+                .unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn select_scoped_restores_previous_selection() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+            ];
+            let mut switch = new(&transactions);
+            {
+                let mut scoped = switch.select_scoped(0x02).unwrap();
+                scoped.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+                assert_eq!(Some(0x02), scoped.cached_channel_status());
+            }
+            assert_eq!(Some(0x01), switch.cached_channel_status());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn with_channels_writes_even_if_cached() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x02).unwrap();
+            switch.with_channels(0x02, |_| ()).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn select_scoped_writes_even_if_cached() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::read(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
+            drop(switch.select_scoped(0x01).unwrap());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn toggle_channel_writes_even_if_cached() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::read(DEV_ADDR, vec![0x00]),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0x01).unwrap();
+            assert!(switch.toggle_channel(Channel::Ch0).unwrap());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn with_channels_restores_selection_when_closure_fails() {
+            let transactions = [
+                I2cTrans::read(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+            ];
+            let mut switch = new(&transactions);
+            let result = switch.with_channels(0x02, |_| Err::<(), ()>(())).unwrap();
+            assert_eq!(Err(()), result);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_get_channel_status() {
+            let transactions = [I2cTrans::read(DEV_ADDR, vec![0b0101_0101 & $channels])];
+            let switch = new(&transactions);
+            let read_status = switch.get_channel_status().unwrap();
+            assert_eq!(0b0101_0101 & $channels, read_status);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn get_channel_status_only_reads() {
+            let transactions = [I2cTrans::read(DEV_ADDR, vec![0x01])];
+            let mut switch = new(&transactions);
+            switch.recover();
+            assert_eq!(0x01, switch.get_channel_status().unwrap());
+            assert_eq!(None, switch.cached_channel_status());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn probe_only_reads() {
+            let transactions = [I2cTrans::read(DEV_ADDR, vec![0x01])];
+            let mut switch = new(&transactions);
+            switch.recover();
+            assert_eq!(0x01, switch.probe().unwrap());
+            assert_eq!(None, switch.cached_channel_status());
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_get_channel_status_with_alternative_address() {
+            let transactions = [I2cTrans::read(0b111_0010, vec![0b0101_0101 & $channels])];
+            let switch = $name::new(
+                I2cMock::new(&transactions),
+                SlaveAddr::Alternative(false, true, false),
+            );
+            let read_status = switch.get_channel_status().unwrap();
+            assert_eq!(0b0101_0101 & $channels, read_status);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_write_to_slave() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0b0000_0001).unwrap();
+            switch.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_read_from_slave() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0b0000_0001).unwrap();
+
+            let mut read_data = [0; 2];
+            switch.read(SLAVE_ADDR, &mut read_data).unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+        #[test]
+        fn can_write_read_from_slave() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write_read(
+                    SLAVE_ADDR,
+                    SLAVE_WRITE_DATA.to_vec(),
+                    SLAVE_READ_DATA.to_vec(),
+                ),
+            ];
+            let mut switch = new(&transactions);
+            switch.select_channels(0b0000_0001).unwrap();
+
+            let mut read_data = [0; 2];
+            switch
+                .write_read(SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data)
+                .unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_split_and_communicate_with_slave() {
+            let slave_read_data_2 = [0xAB, 0xCD];
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write_read(
+                    SLAVE_ADDR,
+                    SLAVE_WRITE_DATA.to_vec(),
+                    slave_read_data_2.to_vec(),
+                ),
+            ];
+            let switch = new(&transactions);
+            {
+                let mut read_data_1 = [0; 2];
+                let mut read_data_2 = [0; 2];
+                let mut parts = switch.split();
+                parts.i2c0.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+                parts.i2c1.read(SLAVE_ADDR, &mut read_data_1).unwrap();
+                parts
+                    .i2c0
+                    .write_read(SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data_2)
+                    .unwrap();
+                assert_eq!(read_data_1, SLAVE_READ_DATA);
+                assert_eq!(read_data_2, slave_read_data_2);
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn can_drive_split_slaves_interleaved() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write_read(SLAVE_ADDR, vec![0x10], vec![0xAB]),
+                I2cTrans::write(DEV_ADDR, vec![0x02]),
+                I2cTrans::write_read(SLAVE_ADDR, vec![0x10], vec![0xCD]),
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write_read(SLAVE_ADDR, vec![0x20], vec![0x12]),
+            ];
+            let switch = new(&transactions);
+            {
+                let parts = switch.split();
+                let mut driver0 = Driver::new(parts.i2c0);
+                let mut driver1 = Driver::new(parts.i2c1);
+                assert_eq!(0xAB, driver0.read_register(0x10).unwrap());
+                assert_eq!(0xCD, driver1.read_register(0x10).unwrap());
+                assert_eq!(0x12, driver0.read_register(0x20).unwrap());
+            }
+            switch.destroy().done();
+        }
+
+        #[test]
+        fn when_split_only_change_channel_if_necessary() {
+            let transactions = [
+                I2cTrans::write(DEV_ADDR, vec![0x01]),
+                I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+                I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            ];
+            let switch = new(&transactions);
+            {
+                let mut read_data = [0; 2];
+                let mut parts = switch.split();
+                parts.i2c0.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+                parts.i2c0.read(SLAVE_ADDR, &mut read_data).unwrap();
+                assert_eq!(read_data, SLAVE_READ_DATA);
+            }
+            switch.destroy().done();
+        }
+    };
+}
+
+mod test_xca9548a {
+    use super::*;
+    test_device!(Xca9548a, 0xff);
+
+    #[test]
+    fn select_channels_with_channel_3_enables_only_channel_3() {
+        let transactions = [I2cTrans::write(DEV_ADDR, vec![0b0000_1000])];
+        let mut switch = new(&transactions);
+        switch.select_channels(Channel::Ch3).unwrap();
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn reject_channel_index_8() {
+        let mut switch = new(&[]);
+        match switch.select_channel_index(8) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel index not reported"),
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_select_channel_set_with_duplicates() {
+        let transactions = [I2cTrans::write(DEV_ADDR, vec![0b0001_0010])];
+        let mut switch = new(&transactions);
+        switch
+            .select_channel_set(&[Channel::Ch1, Channel::Ch1, Channel::Ch4])
+            .unwrap();
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_toggle_channel() {
+        let transactions = [
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0000]),
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0100]),
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0100]),
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0000]),
+        ];
+        let mut switch = new(&transactions);
+        assert!(switch.toggle_channel(Channel::Ch2).unwrap());
+        assert!(!switch.toggle_channel(Channel::Ch2).unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_broadcast_write() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0001]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0100]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0b1000_0000]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let mut switch = new(&transactions);
+        switch
+            .broadcast_write(0b1000_0101, SLAVE_ADDR, &SLAVE_WRITE_DATA)
+            .unwrap();
+        assert_eq!(Some(0b1000_0000), switch.cached_channel_status());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_get_channel_delta() {
+        let transactions = [
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0011]),
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0101]),
+        ];
+        let switch = new(&transactions);
+        assert_eq!(
+            (0b0000_0100, 0b0000_0010),
+            switch.channel_delta(0b0000_0101).unwrap()
+        );
+        assert_eq!((0, 0), switch.channel_delta(0b0000_0101).unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn broadcast_write_verified_reports_failing_channel() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0001]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write_read(SLAVE_ADDR, vec![0x10], SLAVE_READ_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0100]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write_read(SLAVE_ADDR, vec![0x10], vec![0, 0]),
+            I2cTrans::write(DEV_ADDR, vec![0b1000_0000]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write_read(SLAVE_ADDR, vec![0x10], SLAVE_READ_DATA.to_vec()),
+        ];
+        let mut switch = new(&transactions);
+        let passed = switch
+            .broadcast_write_verified(
+                0b1000_0101,
+                SLAVE_ADDR,
+                &SLAVE_WRITE_DATA,
+                0x10,
+                &SLAVE_READ_DATA,
+            )
+            .unwrap();
+        assert_eq!(0b1000_0001, passed);
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn broadcast_write_verified_rejects_too_long_readback() {
+        let mut switch = new(&[]);
+        let expected = [0; Xca9548a::<I2cMock>::MAX_READBACK_LEN + 1];
+        match switch.broadcast_write_verified(0x01, SLAVE_ADDR, &SLAVE_WRITE_DATA, 0x10, &expected)
+        {
+            Err(Error::BufferTooLong) => {}
+            _ => panic!("Too long readback not reported"),
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_select_channel_3() {
+        let transactions = [I2cTrans::write(DEV_ADDR, vec![0b0000_1000])];
+        let mut switch = new(&transactions);
+        switch.select_channel(Channel::Ch3).unwrap();
+        switch.destroy().done();
+    }
+}
+
+mod test_xca9545a {
+    use super::*;
+    test_device!(Xca9545a, 0x0f);
+    test_interrupt!(Xca9545a, 0x0f);
+    test_ch_out_of_range!(Xca9545a, 0x0f);
+
+    #[test]
+    fn interrupt_status_ignores_selected_channels() {
+        let transactions = [I2cTrans::read(DEV_ADDR, vec![0b1010_0011])];
+        let switch = new(&transactions);
+        assert_eq!(0b1010, switch.get_interrupt_status().unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_get_status() {
+        let transactions = [I2cTrans::read(DEV_ADDR, vec![0b1010_0011])];
+        let switch = new(&transactions);
+        let status = switch.get_status().unwrap();
+        assert!(status.channel_enabled(Channel::Ch0));
+        assert!(status.channel_enabled(Channel::Ch1));
+        assert!(!status.channel_enabled(Channel::Ch2));
+        assert!(!status.interrupt_pending(Channel::Ch0));
+        assert!(status.interrupt_pending(Channel::Ch1));
+        assert!(status.interrupt_pending(Channel::Ch3));
+        assert_eq!(Channels::CH0 | Channels::CH1, status.channels());
+        assert_eq!(Channels::CH1 | Channels::CH3, status.interrupts());
+        switch.destroy().done();
+    }
+}
+
+mod test_xca9546a {
+    use super::*;
+    test_device!(Xca9546a, 0x0f);
+    test_ch_out_of_range!(Xca9546a, 0x0f);
+
+    #[test]
+    fn reject_ch4() {
+        let mut switch = new(&[]);
+        match switch.select_channels(0b0001_0000) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel mask not reported"),
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn has_max_channel() {
+        assert_eq!(Channel::Ch3, Xca9546a::<I2cMock>::MAX_CHANNEL);
+    }
+
+    #[test]
+    fn reject_ch6() {
+        let mut switch = new(&[]);
+        match switch.select_channel(Channel::Ch6) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel not reported"),
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn reject_ch6_in_set() {
+        let mut switch = new(&[]);
+        match switch.select_channel_set(&[Channel::Ch0, Channel::Ch6]) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel not reported"),
+        }
+        switch.destroy().done();
+    }
+}
+
+mod test_xca9543a {
+    use super::*;
+    test_device!(Xca9543a, 0x03);
+    test_interrupt!(Xca9543a, 0x03);
+    test_ch_out_of_range!(Xca9543a, 0x03);
+
+    fn address(a1: bool, a0: bool) -> u8 {
+        let switch = Xca9543a::new(I2cMock::new(&[]), SlaveAddr::Alternative(false, a1, a0));
+        let address = switch.address();
+        switch.destroy().done();
+        address
+    }
+
+    #[test]
+    fn can_generate_addresses_from_a1_a0() {
+        assert_eq!(0b111_0000, address(false, false));
+        assert_eq!(0b111_0001, address(false, true));
+        assert_eq!(0b111_0010, address(true, false));
+        assert_eq!(0b111_0011, address(true, true));
+    }
+
+    #[test]
+    fn can_select_channel_with_a1_a0_address() {
+        let transactions = [I2cTrans::write(0b111_0011, vec![0x02])];
+        let mut switch = Xca9543a::new(
+            I2cMock::new(&transactions),
+            SlaveAddr::Alternative(false, true, true),
+        );
+        switch.select_channel(Channel::Ch1).unwrap();
+        switch.destroy().done();
+    }
+}
+
+mod errors {
+    use xca9548a::Error;
+
+    #[test]
+    fn can_get_i2c_error() {
+        let error: Error<&str> = Error::I2C("NACK");
+        assert_eq!(Some(&"NACK"), error.as_i2c_error());
+        assert!(!error.is_acquire_error());
+    }
+
+    #[test]
+    fn can_check_acquire_error() {
+        let error: Error<&str> = Error::CouldNotAcquireDevice;
+        assert!(error.is_acquire_error());
+        assert_eq!(None, error.as_i2c_error());
+    }
+
+    #[test]
+    fn can_display_i2c_error() {
+        let error: Error<&str> = Error::I2C("NACK");
+        assert_eq!("I2C bus error: NACK", format!("{}", error));
+    }
+
+    #[test]
+    fn can_display_address_conflict_error() {
+        let error: Error<&str> = Error::AddressConflict;
+        assert_eq!(
+            "slave address conflicts with device address or channel",
+            format!("{}", error)
+        );
+    }
+
+    #[test]
+    fn can_display_verification_error() {
+        let error: Error<&str> = Error::VerificationFailed {
+            expected: 0b0000_0001,
+            actual: 0b0000_0010,
+        };
+        assert_eq!(
+            "channel selection verification failed: wrote 0b00000001, read 0b00000010",
+            format!("{}", error)
+        );
+    }
+
+    #[test]
+    fn can_display_interrupt_pin_error() {
+        let error: Error<&str> = Error::InterruptPin;
+        assert_eq!("could not read interrupt pin", format!("{}", error));
+    }
+
+    #[test]
+    fn can_display_buffer_too_long_error() {
+        let error: Error<&str> = Error::BufferTooLong;
+        assert_eq!("buffer too long", format!("{}", error));
+    }
+
+    #[test]
+    fn can_display_acquire_error() {
+        let error: Error<&str> = Error::CouldNotAcquireDevice;
+        assert_eq!("could not acquire device", format!("{}", error));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn i2c_error_has_source() {
+        use std::error::Error as StdError;
+        let error = Error::I2C(std::fmt::Error);
+        assert!(error.source().is_some());
+        assert!(Error::<std::fmt::Error>::CouldNotAcquireDevice
+            .source()
+            .is_none());
+    }
+}
+
+mod test_xca9544a {
+    use super::*;
+
+    fn new(transactions: &[I2cTrans]) -> Xca9544a<I2cMock> {
+        Xca9544a::new(I2cMock::new(transactions), SlaveAddr::default())
+    }
+
+    #[test]
+    fn can_select_channel() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0100]),
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0111]),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch0).unwrap();
+        mux.select_channel(Channel::Ch3).unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_read_raw_control_register() {
+        let transactions = [I2cTrans::read(DEV_ADDR, vec![0b1010_0110])];
+        let mux = new(&transactions);
+        assert_eq!(0b1010_0110, mux.read_control_register().unwrap());
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn disables_channel_on_drop() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0101]),
+            I2cTrans::write(DEV_ADDR, vec![0x00]),
+        ];
+        let mut i2c = I2cMock::new(&transactions);
+        let mut mux = Xca9544a::new(i2c.clone(), SlaveAddr::default()).disable_on_drop();
+        mux.select_channel(Channel::Ch1).unwrap();
+        drop(mux);
+        i2c.done();
+    }
+
+    #[test]
+    fn reject_ch4() {
+        let mut mux = new(&[]);
+        match mux.select_channel(Channel::Ch4) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel not reported"),
+        }
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_disable() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0101]),
+            I2cTrans::write(DEV_ADDR, vec![0]),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch1).unwrap();
+        mux.disable().unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_get_selected_channel() {
+        let transactions = [
+            I2cTrans::read(DEV_ADDR, vec![0b1010_0110]),
+            I2cTrans::read(DEV_ADDR, vec![0b1010_0010]),
+        ];
+        let mux = new(&transactions);
+        assert_eq!(Some(Channel::Ch2), mux.get_selected_channel().unwrap());
+        assert_eq!(None, mux.get_selected_channel().unwrap());
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_get_interrupt_status() {
+        let transactions = [I2cTrans::read(DEV_ADDR, vec![0b1010_0110])];
+        let mux = new(&transactions);
+        assert_eq!(0b1010, mux.get_interrupt_status().unwrap());
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_get_status() {
+        let transactions = [I2cTrans::read(DEV_ADDR, vec![0b1010_0110])];
+        let mux = new(&transactions);
+        let status = mux.get_status().unwrap();
+        assert_eq!(Channels::CH2, status.channels());
+        assert!(status.channel_enabled(Channel::Ch2));
+        assert!(!status.channel_enabled(Channel::Ch0));
+        assert_eq!(Channels::CH1 | Channels::CH3, status.interrupts());
+        assert!(status.interrupt_pending(Channel::Ch3));
+        mux.destroy().done();
+    }
+}
+
+mod test_pca9542a {
+    use super::*;
+
+    fn new(transactions: &[I2cTrans]) -> Pca9542a<I2cMock> {
+        Pca9542a::new(I2cMock::new(transactions), SlaveAddr::default())
+    }
+
+    #[test]
+    fn can_select_channel() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0100]),
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0101]),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch0).unwrap();
+        mux.select_channel(Channel::Ch1).unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn reject_ch2() {
+        let mut mux = new(&[]);
+        match mux.select_channel(Channel::Ch2) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel not reported"),
+        }
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_disable() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0101]),
+            I2cTrans::write(DEV_ADDR, vec![0]),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch1).unwrap();
+        mux.disable().unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_get_selected_channel() {
+        let transactions = [I2cTrans::read(DEV_ADDR, vec![0b0001_0101])];
+        let mux = new(&transactions);
+        assert_eq!(Some(Channel::Ch1), mux.get_selected_channel().unwrap());
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_get_interrupt_status() {
+        let transactions = [I2cTrans::read(DEV_ADDR, vec![0b1101_0100])];
+        let mux = new(&transactions);
+        assert_eq!(0b01, mux.get_interrupt_status().unwrap());
+        mux.destroy().done();
+    }
+}
+
+mod test_pca9540b {
+    use super::*;
+
+    fn new(transactions: &[I2cTrans]) -> Pca9540b<I2cMock> {
+        Pca9540b::new(I2cMock::new(transactions), SlaveAddr::default())
+    }
+
+    #[test]
+    fn can_select_channel() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0101]),
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0100]),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch1).unwrap();
+        mux.select_channel(Channel::Ch0).unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_disable() {
+        let transactions = [I2cTrans::write(DEV_ADDR, vec![0])];
+        let mut mux = new(&transactions);
+        mux.disable().unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_get_selected_channel() {
+        let transactions = [
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0100]),
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0001]),
+        ];
+        let mux = new(&transactions);
+        assert_eq!(Some(Channel::Ch0), mux.get_selected_channel().unwrap());
+        assert_eq!(None, mux.get_selected_channel().unwrap());
+        mux.destroy().done();
+    }
+}
+
+mod test_ltc4306 {
+    use super::*;
+
+    const LTC_ADDR: u8 = 0b100_0100;
+
+    fn new(transactions: &[I2cTrans]) -> Ltc4306<I2cMock> {
+        Ltc4306::new(I2cMock::new(transactions), LTC_ADDR)
+    }
+
+    #[test]
+    fn can_get_8_bit_addresses() {
+        let ltc = new(&[]);
+        assert_eq!(0x88, ltc.write_address());
+        assert_eq!(0x89, ltc.read_address());
+        ltc.destroy().done();
+    }
+
+    #[test]
+    fn can_select_channels() {
+        let transactions = [
+            I2cTrans::write(LTC_ADDR, vec![3, 0b1000_0000]),
+            I2cTrans::write(LTC_ADDR, vec![3, 0b1010_0000]),
+            I2cTrans::write(LTC_ADDR, vec![3, 0b0001_0000]),
+        ];
+        let mut switch = new(&transactions);
+        switch.select_channels(Channels::CH0).unwrap();
+        switch.select_channels(0b0101).unwrap();
+        switch.select_channels(0b0101).unwrap();
+        switch.select_channels(Channels::CH3).unwrap();
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn reject_ch_out_of_range() {
+        let mut switch = new(&[]);
+        match switch.select_channels(0b0001_0000) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel mask not reported"),
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_get_channel_status() {
+        let transactions = [
+            I2cTrans::write_read(LTC_ADDR, vec![3], vec![0b1000_0110]),
+            I2cTrans::write_read(LTC_ADDR, vec![3], vec![0b0011_0110]),
+        ];
+        let switch = new(&transactions);
+        assert_eq!(0b0001, switch.get_channel_status().unwrap());
+        assert_eq!(0b1100, switch.get_channel_status().unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_get_bus_stuck_flags() {
+        let transactions = [
+            I2cTrans::write_read(LTC_ADDR, vec![0], vec![0b1000_0010]),
+            I2cTrans::write_read(LTC_ADDR, vec![0], vec![0b0000_0100]),
+            I2cTrans::write_read(LTC_ADDR, vec![0], vec![0b1111_1001]),
+        ];
+        let switch = new(&transactions);
+        assert_eq!((true, false), switch.bus_stuck().unwrap());
+        assert_eq!((false, true), switch.bus_stuck().unwrap());
+        assert_eq!((false, false), switch.bus_stuck().unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_write_to_slave() {
+        let transactions = [
+            I2cTrans::write(LTC_ADDR, vec![3, 0b1000_0000]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let mut switch = new(&transactions);
+        switch.select_channels(0b0001).unwrap();
+        switch.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+        switch.destroy().done();
+    }
+}
+
+mod test_pca9547 {
+    use super::*;
+
+    fn new(transactions: &[I2cTrans]) -> Pca9547<I2cMock> {
+        Pca9547::new(I2cMock::new(transactions), SlaveAddr::default())
+    }
+
+    #[test]
+    fn can_select_channel() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_1000]),
+            I2cTrans::write(DEV_ADDR, vec![0b0000_1101]),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch0).unwrap();
+        mux.select_channel(Channel::Ch5).unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_reset() {
+        let pin_transactions = [PinTrans::set(PinState::Low), PinTrans::set(PinState::High)];
+        let mut reset_pin = PinMock::new(&pin_transactions);
+        let mut mux = new(&[]);
+        mux.reset(&mut reset_pin, &mut NoopDelay::new()).unwrap();
+        mux.destroy().done();
+        reset_pin.done();
+    }
+
+    #[test]
+    fn can_disable_all() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_1111]),
+            I2cTrans::write(DEV_ADDR, vec![0]),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch7).unwrap();
+        mux.disable_all().unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_disable() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_1001]),
+            I2cTrans::write(DEV_ADDR, vec![0]),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch1).unwrap();
+        mux.disable().unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_get_selected_channel() {
+        let transactions = [
+            I2cTrans::read(DEV_ADDR, vec![0b0000_1110]),
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0110]),
+        ];
+        let mux = new(&transactions);
+        assert_eq!(Some(Channel::Ch6), mux.get_selected_channel().unwrap());
+        assert_eq!(None, mux.get_selected_channel().unwrap());
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn can_write_to_slave() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_1010]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let mut mux = new(&transactions);
+        mux.select_channel(Channel::Ch2).unwrap();
+        mux.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+        mux.destroy().done();
+    }
+}
+
+#[cfg(feature = "async")]
+mod test_async {
+    use super::{DEV_ADDR, SLAVE_ADDR, SLAVE_READ_DATA, SLAVE_WRITE_DATA};
+    use core::future::Future;
+    use core::pin::pin;
+    use core::task::{Context, Poll, Waker};
+    use embedded_hal_async::i2c::{I2c, Operation};
+    use hal::eh1::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+    use xca9548a::asynch::{Xca9543a, Xca9548a};
+    use xca9548a::{Config, Error, SlaveAddr};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    fn new(transactions: &[I2cTrans]) -> Xca9548a<I2cMock> {
+        Xca9548a::new(I2cMock::new(transactions), SlaveAddr::default())
+    }
+
+    #[test]
+    fn can_select_channels() {
+        let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+        let mut switch = new(&transactions);
+        block_on(switch.select_channels(0x01)).unwrap();
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_get_channel_status() {
+        let transactions = [I2cTrans::read(DEV_ADDR, vec![0b0101_0101])];
+        let mut switch = new(&transactions);
+        assert_eq!(0b0101_0101, block_on(switch.get_channel_status()).unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn reject_ch_out_of_range() {
+        let mut switch = Xca9543a::new(I2cMock::new(&[]), SlaveAddr::default());
+        match block_on(switch.select_channels(0b0000_0100)) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel mask not reported"),
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_write_read_from_slave() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write_read(
+                SLAVE_ADDR,
+                SLAVE_WRITE_DATA.to_vec(),
+                SLAVE_READ_DATA.to_vec(),
+            ),
+        ];
+        let mut switch = new(&transactions);
+        let mut read_data = [0; 2];
+        block_on(async {
+            switch.select_channels(0x01).await?;
+            switch
+                .write_read(SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data)
+                .await
+        })
+        .unwrap();
+        assert_eq!(read_data, SLAVE_READ_DATA);
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_forward_transaction() {
+        let transactions = [
+            I2cTrans::transaction_start(SLAVE_ADDR),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            I2cTrans::transaction_end(SLAVE_ADDR),
+        ];
+        let mut switch = new(&transactions);
+        let mut read_data = [0; 2];
+        let mut operations = [
+            Operation::Write(&SLAVE_WRITE_DATA),
+            Operation::Read(&mut read_data),
+        ];
+        block_on(switch.transaction(SLAVE_ADDR, &mut operations)).unwrap();
+        assert_eq!(read_data, SLAVE_READ_DATA);
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn rejects_own_address() {
+        let mut switch = new(&[]);
+        let mut read_data = [0; 2];
+        match block_on(switch.write(DEV_ADDR, &SLAVE_WRITE_DATA)) {
+            Err(Error::AddressConflict) => {}
+            _ => panic!("Address conflict not reported"),
+        }
+        match block_on(switch.read(DEV_ADDR, &mut read_data)) {
+            Err(Error::AddressConflict) => {}
+            _ => panic!("Address conflict not reported"),
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_allow_own_address() {
+        let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01])];
+        let config = Config {
+            allow_own_address: true,
+            ..Config::default()
+        };
+        let mut switch =
+            Xca9548a::new_with_config(I2cMock::new(&transactions), SlaveAddr::default(), config);
+        block_on(switch.write(DEV_ADDR, &[0x01])).unwrap();
+        switch.destroy().done();
+    }
+}
+
+#[cfg(feature = "eh1")]
+mod test_eh1 {
+    use super::{DEV_ADDR, SLAVE_ADDR, SLAVE_READ_DATA, SLAVE_WRITE_DATA};
+    use embedded_hal_1::i2c::{Error as _, ErrorKind, I2c, Operation};
+    use hal::eh1::i2c::{Mock as I2cMock, Transaction as I2cTrans};
+    use xca9548a::{Error, Ltc4306, SlaveAddr, Xca9544a, Xca9548a};
+
+    fn new(transactions: &[I2cTrans]) -> Xca9548a<I2cMock> {
+        Xca9548a::new(I2cMock::new(transactions), SlaveAddr::default())
+    }
+
+    #[test]
+    fn can_select_channels_and_get_status() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0101]),
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0101]),
+        ];
+        let mut switch = new(&transactions);
+        switch.select_channels_eh1(0b0000_0101).unwrap();
+        // Channels are already selected
+        switch.select_channels_eh1(0b0000_0101).unwrap();
+        assert_eq!(0b0000_0101, switch.get_channel_status_eh1().unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn mux_selects_single_channel() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0110]),
+            I2cTrans::read(DEV_ADDR, vec![0b0101_0110]),
+        ];
+        let mut mux = Xca9544a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        mux.select_channels_eh1(0b0000_0100).unwrap();
+        match mux.select_channels_eh1(0b0000_0011) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Several channels not rejected"),
+        }
+        assert_eq!(0b0000_0100, mux.get_channel_status_eh1().unwrap());
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn ltc4306_can_select_channels_and_get_status() {
+        let ltc_addr = 0b100_0100;
+        let transactions = [
+            I2cTrans::write(ltc_addr, vec![3, 0b1001_0000]),
+            I2cTrans::write_read(ltc_addr, vec![3], vec![0b1001_0110]),
+        ];
+        let mut switch = Ltc4306::new(I2cMock::new(&transactions), ltc_addr);
+        switch.select_channels_eh1(0b1001).unwrap();
+        assert_eq!(0b1001, switch.get_channel_status_eh1().unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_write_read_transparently() {
+        let transactions = [I2cTrans::write_read(
+            SLAVE_ADDR,
+            SLAVE_WRITE_DATA.to_vec(),
+            SLAVE_READ_DATA.to_vec(),
+        )];
+        let mut switch = new(&transactions);
+        let mut read_data = [0; 2];
+        switch
+            .write_read(SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data)
+            .unwrap();
+        assert_eq!(read_data, SLAVE_READ_DATA);
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_forward_transaction() {
+        let transactions = [
+            I2cTrans::transaction_start(SLAVE_ADDR),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            I2cTrans::transaction_end(SLAVE_ADDR),
+        ];
+        let mut switch = new(&transactions);
+        let mut read_data = [0; 2];
+        let mut operations = [
+            Operation::Write(&SLAVE_WRITE_DATA),
+            Operation::Read(&mut read_data),
+        ];
+        switch.transaction(SLAVE_ADDR, &mut operations).unwrap();
+        assert_eq!(read_data, SLAVE_READ_DATA);
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn slave_selects_channel_before_transaction() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x02]),
+            I2cTrans::transaction_start(SLAVE_ADDR),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            I2cTrans::transaction_end(SLAVE_ADDR),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let switch = new(&transactions);
+        {
+            let mut parts = switch.split();
+            let mut read_data = [0; 2];
+            let mut operations = [
+                Operation::Write(&SLAVE_WRITE_DATA),
+                Operation::Read(&mut read_data),
+            ];
+            parts.i2c1.transaction(SLAVE_ADDR, &mut operations).unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+            // Channel is already selected
+            parts.i2c1.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+        }
+        switch.destroy().done();
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn can_drive_owned_slaves() {
+        use xca9548a::OwnedParts;
+
+        /// Driver generic over the `embedded-hal` 1.0 I2C trait.
+        fn write_data<I: I2c>(i2c: &mut I) -> Result<(), I::Error> {
+            i2c.write(SLAVE_ADDR, &SLAVE_WRITE_DATA)
+        }
+
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0x02]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let OwnedParts {
+            mut i2c0, mut i2c1, ..
+        } = new(&transactions).into_channels();
+        write_data(&mut i2c0).unwrap();
+        write_data(&mut i2c1).unwrap();
+        assert!(i2c0.into_device().is_none());
+        i2c1.into_device().unwrap().destroy().done();
+    }
+
+    #[test]
+    fn slave_reports_error_kind() {
+        let transactions = [I2cTrans::write(DEV_ADDR, vec![0x01]).with_error(ErrorKind::Bus)];
+        let switch = new(&transactions);
+        {
+            let mut parts = switch.split();
+            match parts.i2c0.write(SLAVE_ADDR, &SLAVE_WRITE_DATA) {
+                Err(e @ Error::I2C(_)) => assert_eq!(e.kind(), ErrorKind::Bus),
+                _ => panic!("Error not reported"),
+            }
+        }
+        switch.destroy().done();
+    }
+}
+
+mod transactional {
+    use super::{DEV_ADDR, SLAVE_ADDR, SLAVE_READ_DATA, SLAVE_WRITE_DATA};
+    use embedded_hal::blocking::i2c::{Operation, Transactional, Write};
+    use xca9548a::{SlaveAddr, Xca9548a};
+
+    /// Bus recording writes and answering reads with `SLAVE_READ_DATA`,
+    /// since the I2C mock does not support transactions.
+    #[derive(Default)]
+    struct Bus {
+        writes: Vec<(u8, Vec<u8>)>,
+        reads: Vec<u8>,
+    }
+
+    impl Write for Bus {
+        type Error = ();
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+            self.writes.push((address, bytes.to_vec()));
+            Ok(())
+        }
+    }
+
+    impl Transactional for Bus {
+        type Error = ();
+
+        fn exec(&mut self, address: u8, operations: &mut [Operation]) -> Result<(), ()> {
+            for operation in operations {
+                match operation {
+                    Operation::Write(bytes) => self.writes.push((address, bytes.to_vec())),
+                    Operation::Read(buffer) => {
+                        buffer.copy_from_slice(&SLAVE_READ_DATA);
+                        self.reads.push(address);
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn can_forward_transaction() {
+        let mut switch = Xca9548a::new(Bus::default(), SlaveAddr::default());
+        let mut read_data = [0; 2];
+        let mut operations = [
+            Operation::Write(&SLAVE_WRITE_DATA),
+            Operation::Read(&mut read_data),
+        ];
+        switch.exec(SLAVE_ADDR, &mut operations).unwrap();
+        assert_eq!(read_data, SLAVE_READ_DATA);
+        let bus = switch.destroy();
+        assert_eq!(bus.writes, [(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec())]);
+        assert_eq!(bus.reads, [SLAVE_ADDR]);
+    }
+
+    #[test]
+    fn slave_selects_channel_before_transaction() {
+        let switch = Xca9548a::new(Bus::default(), SlaveAddr::default());
+        {
+            let mut parts = switch.split();
+            let mut read_data = [0; 2];
+            let mut operations = [
+                Operation::Write(&SLAVE_WRITE_DATA),
+                Operation::Read(&mut read_data),
+            ];
+            parts.i2c2.exec(SLAVE_ADDR, &mut operations).unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+        }
+        let bus = switch.destroy();
+        assert_eq!(
+            bus.writes,
+            [
+                (DEV_ADDR, vec![0x04]),
+                (SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec())
+            ]
+        );
+        assert_eq!(bus.reads, [SLAVE_ADDR]);
+    }
+}
+
+mod write_iter {
+    use super::{I2cMock, I2cTrans, DEV_ADDR, SLAVE_ADDR, SLAVE_READ_DATA, SLAVE_WRITE_DATA};
+    use embedded_hal::blocking::i2c::{WriteIter, WriteIterRead};
+    use xca9548a::{SlaveAddr, Xca9548a};
+
+    #[test]
+    fn can_write_from_iterator() {
+        let transactions = [I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec())];
+        let mut switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        WriteIter::write(&mut switch, SLAVE_ADDR, SLAVE_WRITE_DATA.iter().cloned()).unwrap();
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn slave_selects_channel_before_writing_from_iterator() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x02]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        {
+            let mut parts = switch.split();
+            WriteIter::write(
+                &mut parts.i2c1,
+                SLAVE_ADDR,
+                SLAVE_WRITE_DATA.iter().cloned(),
+            )
+            .unwrap();
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn slave_can_write_from_iterator_and_read() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x02]),
+            I2cTrans::write_read(
+                SLAVE_ADDR,
+                SLAVE_WRITE_DATA.to_vec(),
+                SLAVE_READ_DATA.to_vec(),
+            ),
+        ];
+        let switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        {
+            let mut parts = switch.split();
+            let mut read_data = [0; 2];
+            parts
+                .i2c1
+                .write_iter_read(SLAVE_ADDR, SLAVE_WRITE_DATA.iter().cloned(), &mut read_data)
+                .unwrap();
+            assert_eq!(read_data, SLAVE_READ_DATA);
+        }
+        switch.destroy().done();
+    }
+}
+
+mod ten_bit {
+    use super::{DEV_ADDR, SLAVE_WRITE_DATA};
+    use embedded_hal::blocking::i2c::{TenBitAddress, Write};
+    use xca9548a::{SlaveAddr, Xca9548a};
+
+    /// 10-bit address equal to the switch address once truncated.
+    const SLAVE_ADDR_10: u16 = 0x370;
+
+    /// Bus recording 7-bit and 10-bit writes separately.
+    #[derive(Default)]
+    struct Bus {
+        writes: Vec<(u8, Vec<u8>)>,
+        ten_bit_writes: Vec<(u16, Vec<u8>)>,
+    }
+
+    impl Write for Bus {
+        type Error = ();
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), ()> {
+            self.writes.push((address, bytes.to_vec()));
+            Ok(())
+        }
+    }
+
+    impl Write<TenBitAddress> for Bus {
+        type Error = ();
+
+        fn write(&mut self, address: u16, bytes: &[u8]) -> Result<(), ()> {
+            self.ten_bit_writes.push((address, bytes.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn can_forward_ten_bit_write() {
+        let mut switch = Xca9548a::new(Bus::default(), SlaveAddr::default());
+        switch.write(SLAVE_ADDR_10, &SLAVE_WRITE_DATA).unwrap();
+        let bus = switch.destroy();
+        assert!(bus.writes.is_empty());
+        assert_eq!(
+            bus.ten_bit_writes,
+            [(SLAVE_ADDR_10, SLAVE_WRITE_DATA.to_vec())]
+        );
+    }
+
+    #[test]
+    fn slave_selects_channel_before_ten_bit_write() {
+        let switch = Xca9548a::new(Bus::default(), SlaveAddr::default());
+        {
+            let mut parts = switch.split();
+            parts.i2c2.write(SLAVE_ADDR_10, &SLAVE_WRITE_DATA).unwrap();
+        }
+        let bus = switch.destroy();
+        assert_eq!(bus.writes, [(DEV_ADDR, vec![0x04])]);
+        assert_eq!(
+            bus.ten_bit_writes,
+            [(SLAVE_ADDR_10, SLAVE_WRITE_DATA.to_vec())]
+        );
+    }
+}
+
+mod cascade {
+    use super::*;
+
+    const DOWNSTREAM_ADDR: u8 = 0b111_0001;
+
+    #[test]
+    fn can_write_to_slave_two_levels_down() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write(DOWNSTREAM_ADDR, vec![0x08]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let upstream = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        {
+            let upstream_parts = upstream.split();
+            let address = SlaveAddr::Alternative(false, false, true);
+            let downstream = Xca9548a::new(upstream_parts.i2c0, address);
+            let mut downstream_parts = downstream.split();
+            downstream_parts
+                .i2c3
+                .write(SLAVE_ADDR, &SLAVE_WRITE_DATA)
+                .unwrap();
+        }
+        upstream.destroy().done();
+    }
+}
+
+mod mux_scan {
+    use super::*;
+    use xca9548a::scan_for_muxes;
+
+    #[test]
+    fn can_scan_for_muxes() {
+        let transactions: Vec<_> = (0x70..=0x77)
+            .map(|address| {
+                let read = I2cTrans::read(address, vec![0]);
+                if address == 0x72 {
+                    read
+                } else {
+                    read.with_error(BUS_ERROR)
+                }
+            })
+            .collect();
+        let mut i2c = I2cMock::new(&transactions);
+        let found = scan_for_muxes(&mut i2c);
+        assert_eq!(
+            [false, false, true, false, false, false, false, false],
+            found
+        );
+        i2c.done();
+    }
+}
+
+#[cfg(feature = "metrics")]
+mod metrics {
+    use super::*;
+    use xca9548a::MuxStats;
+
+    #[test]
+    fn counts_switches_and_transfers() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::read(SLAVE_ADDR, SLAVE_READ_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0x02]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let mut switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        assert_eq!(Some(MuxStats::default()), switch.stats());
+        switch
+            .write_to(Channel::Ch0, SLAVE_ADDR, &SLAVE_WRITE_DATA)
+            .unwrap();
+        let mut data = [0; 2];
+        // Channel is already selected
+        switch
+            .read_from(Channel::Ch0, SLAVE_ADDR, &mut data)
+            .unwrap();
+        switch
+            .write_to(Channel::Ch1, SLAVE_ADDR, &SLAVE_WRITE_DATA)
+            .unwrap();
+        assert_eq!(
+            Some(MuxStats {
+                switches: 2,
+                transfers: 3,
+            }),
+            switch.stats()
+        );
+        switch.reset_stats();
+        assert_eq!(Some(MuxStats::default()), switch.stats());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn counts_slave_transfers() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x04]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        {
+            let mut parts = switch.split();
+            parts.i2c2.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+            parts.i2c2.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+        }
+        assert_eq!(
+            Some(MuxStats {
+                switches: 1,
+                transfers: 2,
+            }),
+            switch.stats()
+        );
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn counts_each_broadcast_write() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0x04]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0x08]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let mut switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        switch
+            .broadcast_write(0b0000_1101, SLAVE_ADDR, &SLAVE_WRITE_DATA)
+            .unwrap();
+        assert_eq!(
+            Some(MuxStats {
+                switches: 3,
+                transfers: 3,
+            }),
+            switch.stats()
+        );
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn counts_verified_broadcast_write_and_readback() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write_read(SLAVE_ADDR, vec![0x10], SLAVE_READ_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0x02]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write_read(SLAVE_ADDR, vec![0x10], SLAVE_READ_DATA.to_vec()),
+        ];
+        let mut switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        let passed = switch
+            .broadcast_write_verified(0b11, SLAVE_ADDR, &SLAVE_WRITE_DATA, 0x10, &SLAVE_READ_DATA)
+            .unwrap();
+        assert_eq!(0b11, passed);
+        assert_eq!(
+            Some(MuxStats {
+                switches: 2,
+                transfers: 4,
+            }),
+            switch.stats()
+        );
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn counts_raw_access_as_one_transfer() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let mut switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        switch
+            .run_on_channels(0x01, |i2c| i2c.write(SLAVE_ADDR, &SLAVE_WRITE_DATA))
+            .unwrap();
+        assert_eq!(
+            Some(MuxStats {
+                switches: 1,
+                transfers: 1,
+            }),
+            switch.stats()
+        );
+        switch.destroy().done();
+    }
+}
+
+mod variant {
+    use super::*;
+    use xca9548a::Variant;
+
+    #[test]
+    fn new_tca_records_tca_variant() {
+        let switch = Xca9548a::new_tca(I2cMock::new(&[]), SlaveAddr::default());
+        assert_eq!(Some(Variant::Tca), switch.variant());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn new_pca_records_pca_variant() {
+        let switch = Xca9546a::new_pca(I2cMock::new(&[]), SlaveAddr::default());
+        assert_eq!(Some(Variant::Pca), switch.variant());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn mux_records_variant() {
+        let mux = Xca9544a::new_tca(I2cMock::new(&[]), SlaveAddr::default());
+        assert_eq!(Some(Variant::Tca), mux.variant());
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn new_records_no_variant() {
+        let switch = Xca9548a::new(I2cMock::new(&[]), SlaveAddr::default());
+        assert_eq!(None, switch.variant());
+        switch.destroy().done();
+    }
+}
+
+mod channel_config {
+    use super::*;
+
+    #[test]
+    fn allows_any_address_by_default() {
+        let config = ChannelConfig::default();
+        assert!(config.allows(0x00));
+        assert!(config.allows(SLAVE_ADDR));
+        assert!(config.allows(0x7F));
+    }
+
+    #[test]
+    fn allows_only_listed_addresses() {
+        let config = ChannelConfig {
+            allowed_addresses: [Some(0x48), None, Some(0x49), None],
+            ..ChannelConfig::default()
+        };
+        assert!(config.allows(0x48));
+        assert!(config.allows(0x49));
+        assert!(!config.allows(0x4A));
+        assert!(!config.allows(0x00));
+    }
+
+    #[test]
+    fn is_small() {
+        assert!(core::mem::size_of::<ChannelConfig>() <= 12);
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod owned_parts {
+    use super::*;
+    use xca9548a::{OwnedI2cSlave, OwnedParts};
+
+    /// Sensor owning its slave I2C device.
+    struct Sensor(OwnedI2cSlave<Xca9548a<I2cMock>, I2cMock>);
+
+    #[test]
+    fn can_drive_owned_slaves() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write(DEV_ADDR, vec![0x02]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+        ];
+        let switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        let OwnedParts { i2c0, i2c1, .. } = switch.into_channels();
+        let mut sensors = [Sensor(i2c0), Sensor(i2c1)];
+        for sensor in sensors.iter_mut() {
+            sensor.0.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+        }
+        let [first, second] = sensors;
+        assert!(first.0.into_device().is_none());
+        let switch = second.0.into_device().unwrap();
+        switch.destroy().done();
+    }
+}
+
+#[cfg(feature = "alloc")]
+mod mux_chain {
+    use super::*;
+    use xca9548a::MuxChain;
+
+    const SECOND_MUX_ADDR: u8 = 0b111_0001;
+
+    fn new(i2c: &I2cMock) -> MuxChain<I2cMock> {
+        let first = Xca9548a::new(i2c.clone(), SlaveAddr::default());
+        let mut chain = MuxChain::new(vec![first]);
+        let address = SlaveAddr::Alternative(false, false, true);
+        chain.push(Xca9548a::new(i2c.clone(), address));
+        chain
+    }
+
+    #[test]
+    fn can_route_logical_buses() {
+        let mut i2c = I2cMock::new(&[]);
+        let chain = new(&i2c);
+        assert_eq!(16, chain.bus_count());
+        assert_eq!(Some((0, Channel::Ch0)), chain.route(0));
+        assert_eq!(Some((1, Channel::Ch1)), chain.route(9));
+        assert_eq!(None, chain.route(16));
+        i2c.done();
+    }
+
+    #[test]
+    fn can_write_through_two_mux_chain() {
+        let transactions = [
+            I2cTrans::write(SECOND_MUX_ADDR, vec![0x02]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::write(SECOND_MUX_ADDR, vec![0x00]),
+            I2cTrans::write(DEV_ADDR, vec![0x01]),
+            I2cTrans::write_read(
+                SLAVE_ADDR,
+                SLAVE_WRITE_DATA.to_vec(),
+                SLAVE_READ_DATA.to_vec(),
+            ),
+        ];
+        let mut i2c = I2cMock::new(&transactions);
+        let mut chain = new(&i2c);
+        chain.write(9, SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+        let mut read_data = [0; 2];
+        chain
+            .write_read(0, SLAVE_ADDR, &SLAVE_WRITE_DATA, &mut read_data)
+            .unwrap();
+        assert_eq!(read_data, SLAVE_READ_DATA);
+        i2c.done();
+    }
+
+    #[test]
+    fn rejects_missing_logical_bus() {
+        let mut i2c = I2cMock::new(&[]);
+        let mut chain = new(&i2c);
+        match chain.write(16, SLAVE_ADDR, &SLAVE_WRITE_DATA) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Missing logical bus not reported"),
+        }
+        i2c.done();
+    }
+}
+
+mod debug {
+    use super::*;
+
+    /// I2C bus not implementing `Debug`.
+    struct NonDebugI2c;
+
+    #[derive(Debug)]
+    struct Board {
+        switch: Xca9548a<NonDebugI2c>,
+        mux: Xca9544a<NonDebugI2c>,
+    }
+
+    #[test]
+    fn can_debug_print_without_debug_i2c() {
+        let board = Board {
+            switch: Xca9548a::new(NonDebugI2c, SlaveAddr::default()),
+            mux: Xca9544a::new(NonDebugI2c, SlaveAddr::Alternative(false, false, true)),
+        };
+        assert_eq!(
+            "Board { switch: Xca9548a { address: 112, selected_channel_mask: Some(0) }, \
+             mux: Xca9544a { address: 113, selected_channel_mask: Some(0) } }",
+            format!("{:?}", board)
+        );
+        board.switch.destroy();
+        board.mux.destroy();
+    }
+
+    #[test]
+    fn can_debug_print_ltc4306() {
+        let switch = Ltc4306::new(NonDebugI2c, 0b100_0100);
+        assert_eq!(
+            "Ltc4306 { address: 68, selected_channel_mask: None }",
+            format!("{:?}", switch)
+        );
+    }
+}
+
+mod custom_mutex {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::sync::Mutex;
+    use xca9548a::DeviceMutex;
+
+    struct StdMutex<T>(Mutex<T>);
+
+    impl<T> DeviceMutex<T> for StdMutex<T> {
+        fn create(value: T) -> Self {
+            StdMutex(Mutex::new(value))
+        }
+
+        fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+            self.0.try_lock().ok().map(|mut value| f(&mut value))
+        }
+
+        fn get_mut(&mut self) -> &mut T {
+            self.0.get_mut().unwrap()
+        }
+
+        fn into_inner(self) -> T {
+            self.0.into_inner().unwrap()
+        }
+    }
+
+    /// Mutex that is always held by someone else.
+    struct HeldMutex<T>(T);
+
+    impl<T> DeviceMutex<T> for HeldMutex<T> {
+        fn create(value: T) -> Self {
+            HeldMutex(value)
+        }
+
+        fn lock<R>(&self, _f: impl FnOnce(&mut T) -> R) -> Option<R> {
+            None
+        }
+
+        fn get_mut(&mut self) -> &mut T {
+            &mut self.0
+        }
+
+        fn into_inner(self) -> T {
+            self.0
+        }
+    }
+
+    /// Mutex held by someone else for the first few attempts.
+    struct BusyMutex<T>(RefCell<T>, Cell<u8>);
+
+    impl<T> DeviceMutex<T> for BusyMutex<T> {
+        fn create(value: T) -> Self {
+            BusyMutex(RefCell::new(value), Cell::new(2))
+        }
+
+        fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+            match self.1.get() {
+                0 => self.0.lock(f),
+                busy => {
+                    self.1.set(busy - 1);
+                    None
+                }
+            }
+        }
+
+        fn get_mut(&mut self) -> &mut T {
+            self.0.get_mut()
+        }
+
+        fn into_inner(self) -> T {
+            self.0.into_inner()
+        }
+    }
+
+    #[test]
+    fn can_retry_acquiring_device() {
+        let transactions = [I2cTrans::write(DEV_ADDR, vec![0x02])];
+        let switch: Xca9548a<_, BusyMutex<_>> = Xca9548a::new_with_mutex(
+            I2cMock::new(&transactions),
+            SlaveAddr::default(),
+            Config::default(),
+        );
+        let mut waits = 0;
+        switch
+            .select_channels_blocking(0x02, 3, || waits += 1)
+            .unwrap();
+        assert_eq!(2, waits);
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn gives_up_acquiring_device() {
+        let switch: Xca9548a<_, HeldMutex<_>> =
+            Xca9548a::new_with_mutex(I2cMock::new(&[]), SlaveAddr::default(), Config::default());
+        let mut waits = 0;
+        match switch.select_channels_blocking(0x02, 3, || waits += 1) {
+            Err(Error::CouldNotAcquireDevice) => {}
+            _ => panic!("Could not acquire device error not reported"),
+        }
+        assert_eq!(3, waits);
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn slave_reports_held_device() {
+        let switch: Xca9548a<_, HeldMutex<_>> =
+            Xca9548a::new_with_mutex(I2cMock::new(&[]), SlaveAddr::default(), Config::default());
+        {
+            let mut parts = switch.split();
+            match parts.i2c0.write(SLAVE_ADDR, &SLAVE_WRITE_DATA) {
+                Err(Error::CouldNotAcquireDevice) => {}
+                _ => panic!("Could not acquire device error not reported"),
+            }
+        }
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_use_custom_mutex() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0x02]),
+            I2cTrans::write(SLAVE_ADDR, SLAVE_WRITE_DATA.to_vec()),
+            I2cTrans::read(DEV_ADDR, vec![0x02]),
+        ];
+        let switch: Xca9548a<_, StdMutex<_>> = Xca9548a::new_with_mutex(
+            I2cMock::new(&transactions),
+            SlaveAddr::default(),
+            Config::default(),
+        );
+        {
+            let mut parts = switch.split();
+            parts.i2c1.write(SLAVE_ADDR, &SLAVE_WRITE_DATA).unwrap();
+        }
+        assert_eq!(0x02, switch.get_channel_status().unwrap());
+        assert_eq!(DEV_ADDR, switch.address());
+        switch.destroy().done();
+    }
+}
+
+mod i2c_switch {
+    use super::*;
+    use xca9548a::I2cSwitch;
+
+    /// Enable a channel on any device, returning the status read back.
+    fn enable_channel_1<S: I2cSwitch>(switch: &mut S) -> Result<u8, Error<S::BusError>> {
+        switch.select_channels(0b0000_0010)?;
+        switch.get_channel_status()
+    }
+
+    #[test]
+    fn can_use_switch_generically() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0010]),
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0010]),
+        ];
+        let mut switch = Xca9548a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        assert_eq!(0b0000_0010, enable_channel_1(&mut switch).unwrap());
+        switch.destroy().done();
+    }
+
+    #[test]
+    fn can_use_mux_generically() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0b0000_0101]),
+            I2cTrans::read(DEV_ADDR, vec![0b0000_0101]),
+        ];
+        let mut mux = Xca9544a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        assert_eq!(0b0000_0010, enable_channel_1(&mut mux).unwrap());
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn mux_always_writes_deselection() {
+        let transactions = [
+            I2cTrans::write(DEV_ADDR, vec![0]),
+            I2cTrans::write(DEV_ADDR, vec![0]),
+        ];
+        let mut mux = Xca9544a::new(I2cMock::new(&transactions), SlaveAddr::default());
+        I2cSwitch::select_channels(&mut mux, 0).unwrap();
+        I2cSwitch::select_channels(&mut mux, 0).unwrap();
+        mux.destroy().done();
+    }
+
+    #[test]
+    fn mux_rejects_several_channels() {
+        let mut mux = Pca9547::new(I2cMock::new(&[]), SlaveAddr::default());
+        match I2cSwitch::select_channels(&mut mux, 0b0000_0011) {
+            Err(Error::InvalidChannelMask) => {}
+            _ => panic!("Invalid channel mask not reported"),
+        }
+        mux.destroy().done();
+    }
+}
+
+mod mask_properties {
+    use proptest::prelude::*;
+    use std::convert::TryFrom;
+    use xca9548a::{channels_from_mask, mask_from_channels, Channel};
+
+    proptest! {
+        #[test]
+        fn mask_roundtrips_through_channels(mask: u8) {
+            let channels: Vec<Channel> = channels_from_mask(mask).collect();
+            prop_assert_eq!(mask.count_ones() as usize, channels.len());
+            prop_assert_eq!(mask, mask_from_channels(&channels));
+        }
+
+        #[test]
+        fn channels_roundtrip_through_mask(indices in proptest::collection::vec(0u8..8, 0..16)) {
+            let channels: Vec<Channel> = indices
+                .iter()
+                .map(|&index| Channel::try_from(index).unwrap())
+                .collect();
+            let mask = mask_from_channels(&channels);
+            for channel in channels_from_mask(mask) {
+                prop_assert!(channels.contains(&channel));
+            }
+            for channel in channels {
+                prop_assert!(mask & (1 << channel as u8) != 0);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+mod std_mutex {
+    use super::*;
+    use std::sync::Mutex;
+    use std::thread;
+
+    /// Bus recording all writes, since the order of the threads is unknown.
+    #[derive(Default)]
+    struct Recorder(Vec<(u8, Vec<u8>)>);
+
+    impl embedded_hal::blocking::i2c::Write for Recorder {
+        type Error = ();
+
+        fn write(&mut self, address: u8, bytes: &[u8]) -> Result<(), Self::Error> {
+            self.0.push((address, bytes.to_vec()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn diagnostics_do_not_block_while_acquired() {
+        let switch: Xca9548a<_, Mutex<_>> =
+            Xca9548a::new_with_mutex(Recorder::default(), SlaveAddr::default(), Config::default());
+        assert!(switch.is_acquirable());
+        let acquired = switch
+            .with_raw(0x01, |_| {
+                (
+                    switch.is_acquirable(),
+                    switch.cached_channel_status(),
+                    format!("{:?}", switch),
+                )
+            })
+            .unwrap();
+        assert_eq!(
+            (
+                false,
+                None,
+                "Xca9548a { address: 112, selected_channel_mask: None }".to_string()
+            ),
+            acquired
+        );
+        assert_eq!(Some(0x01), switch.cached_channel_status());
+        switch.destroy();
+    }
+
+    #[test]
+    fn can_share_across_threads() {
+        let switch: Xca9548a<_, Mutex<_>> =
+            Xca9548a::new_with_mutex(Recorder::default(), SlaveAddr::default(), Config::default());
+        {
+            let parts = switch.split();
+            let (mut i2c0, mut i2c1) = (parts.i2c0, parts.i2c1);
+            thread::scope(|scope| {
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        i2c0.write(SLAVE_ADDR, &[0]).unwrap();
+                    }
+                });
+                scope.spawn(move || {
+                    for _ in 0..100 {
+                        i2c1.write(SLAVE_ADDR, &[1]).unwrap();
+                    }
+                });
+            });
+        }
+        let writes = switch.destroy().0;
+        let mut selected = None;
+        let mut slave_writes = 0;
+        for (address, bytes) in writes {
+            if address == DEV_ADDR {
+                selected = Some(bytes[0]);
+            } else {
+                assert_eq!(Some(1 << bytes[0]), selected);
+                slave_writes += 1;
+            }
+        }
+        assert_eq!(200, slave_writes);
+    }
+}